@@ -0,0 +1,64 @@
+use crate::BSExp;
+use bytes::Bytes;
+
+/// Binary S-Expression with atoms stored as [`Bytes`]
+/// - Atoms share their buffer with the `Bytes` they were built from
+/// - Converting from and to [`BSExp`] reuses the atom allocations where possible
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BSExpBytes {
+    Atom(Bytes),
+    List(Vec<BSExpBytes>),
+}
+
+impl BSExpBytes {
+    /// Create a new atom without copying the buffer
+    pub fn atom<T: Into<Bytes>>(value: T) -> Self {
+        BSExpBytes::Atom(value.into())
+    }
+
+    /// Create a new list of BSExpBytes
+    pub fn list<T: Into<Vec<BSExpBytes>>>(value: T) -> Self {
+        BSExpBytes::List(value.into())
+    }
+
+    /// Borrow the buffer of an atom, `None` for lists
+    pub fn as_bytes(&self) -> Option<&Bytes> {
+        match self {
+            BSExpBytes::Atom(b) => Some(b),
+            BSExpBytes::List(_) => None,
+        }
+    }
+}
+
+impl From<BSExp> for BSExpBytes {
+    fn from(e: BSExp) -> Self {
+        match e {
+            BSExp::Atom(v) => BSExpBytes::Atom(v.into()),
+            BSExp::List(v) => BSExpBytes::List(v.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+impl From<BSExpBytes> for BSExp {
+    fn from(e: BSExpBytes) -> Self {
+        match e {
+            BSExpBytes::Atom(b) => BSExp::Atom(b.into()),
+            BSExpBytes::List(v) => BSExp::List(v.into_iter().map(Into::into).collect()),
+        }
+    }
+}
+
+#[test]
+fn test_bytes_atom_shares_buffer() {
+    let buf = Bytes::from_static(b"define fib");
+    let e = BSExpBytes::list(vec![
+        BSExpBytes::atom(buf.slice(0..6)),
+        BSExpBytes::atom(buf.slice(7..)),
+    ]);
+    let BSExpBytes::List(v) = &e else {
+        unreachable!()
+    };
+    assert_eq!(v[0].as_bytes().unwrap().as_ptr(), buf.as_ptr());
+    assert_eq!(v[1].as_bytes().unwrap().as_ptr(), buf[7..].as_ptr());
+    assert_eq!(BSExp::from(e).to_string(), "(define fib)");
+}
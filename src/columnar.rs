@@ -0,0 +1,124 @@
+use crate::BSExp;
+use std::collections::HashMap;
+
+/// Kind of a row of [`Columns`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeKind {
+    Atom,
+    List,
+}
+
+/// A forest flattened into parallel columns, one row per node, for
+/// Arrow-style processing
+/// - rows are in pre-order, tree after tree, so a parent always comes
+///   before its children and siblings keep their order
+/// - each distinct atom is stored once in `atom_pool`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Columns {
+    /// Kind of every node
+    pub kind: Vec<NodeKind>,
+    /// Row of the parent of every node, `None` for roots
+    pub parent: Vec<Option<usize>>,
+    /// Index in `atom_pool` of every atom, `None` for lists
+    pub atom: Vec<Option<usize>>,
+    /// Distinct atom payloads, in order of first appearance
+    pub atom_pool: Vec<Vec<u8>>,
+}
+
+impl Columns {
+    /// Number of rows
+    pub fn len(&self) -> usize {
+        self.kind.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.kind.is_empty()
+    }
+
+    /// Rebuild the forest the columns were collected from
+    /// - panics if the columns are inconsistent, e.g. an atom row with no
+    ///   pool index or a parent row that comes after its child
+    pub fn to_forest(&self) -> Vec<BSExp> {
+        let mut children: Vec<Vec<BSExp>> = vec![Vec::new(); self.len()];
+        let mut roots = Vec::new();
+        for i in (0..self.len()).rev() {
+            let node = match self.kind[i] {
+                NodeKind::Atom => BSExp::Atom(self.atom_pool[self.atom[i].unwrap()].clone()),
+                NodeKind::List => {
+                    let mut v = std::mem::take(&mut children[i]);
+                    v.reverse();
+                    BSExp::List(v)
+                }
+            };
+            match self.parent[i] {
+                Some(p) => children[p].push(node),
+                None => roots.push(node),
+            }
+        }
+        roots.reverse();
+        roots
+    }
+}
+
+impl BSExp {
+    /// Flatten a forest into [`Columns`]
+    pub fn collect_into_pool(forest: &[BSExp]) -> Columns {
+        let mut columns = Columns::default();
+        let mut pool_index: HashMap<&[u8], usize> = HashMap::new();
+        let mut stack: Vec<(&BSExp, Option<usize>)> =
+            forest.iter().rev().map(|t| (t, None)).collect();
+        while let Some((node, parent)) = stack.pop() {
+            let row = columns.len();
+            columns.parent.push(parent);
+            match node {
+                BSExp::Atom(a) => {
+                    let i = *pool_index.entry(a).or_insert_with(|| {
+                        columns.atom_pool.push(a.clone());
+                        columns.atom_pool.len() - 1
+                    });
+                    columns.kind.push(NodeKind::Atom);
+                    columns.atom.push(Some(i));
+                }
+                BSExp::List(v) => {
+                    columns.kind.push(NodeKind::List);
+                    columns.atom.push(None);
+                    stack.extend(v.iter().rev().map(|c| (c, Some(row))));
+                }
+            }
+        }
+        columns
+    }
+}
+
+#[test]
+fn test_columns_reconstruct() {
+    use crate::bsexp;
+    let forest = [
+        crate::fib_example(),
+        bsexp!("x"),
+        bsexp!([]),
+        bsexp!(["f", ["x", []], "x"]),
+    ];
+    let columns = BSExp::collect_into_pool(&forest);
+    assert_eq!(columns.to_forest(), forest);
+
+    let tail = BSExp::collect_into_pool(&forest[3..]);
+    assert_eq!(
+        tail.kind,
+        [
+            NodeKind::List,
+            NodeKind::Atom,
+            NodeKind::List,
+            NodeKind::Atom,
+            NodeKind::List,
+            NodeKind::Atom,
+        ]
+    );
+    assert_eq!(
+        tail.parent,
+        [None, Some(0), Some(0), Some(2), Some(2), Some(0)]
+    );
+    assert_eq!(tail.atom, [None, Some(0), None, Some(1), None, Some(1)]);
+    assert_eq!(tail.atom_pool, [b"f".to_vec(), b"x".to_vec()]);
+    assert!(BSExp::collect_into_pool(&[]).to_forest().is_empty());
+}
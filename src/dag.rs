@@ -0,0 +1,353 @@
+use crate::BSExp;
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::hash::{BuildHasher, RandomState};
+use std::mem;
+
+/// A node of a deduplicated forest
+/// - Atoms keep their bytes
+/// - Lists refer to their children by index in the node pool
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Node {
+    Atom(Vec<u8>),
+    List(Vec<usize>),
+}
+
+/// Borrowed form of a [`Node`], to look nodes up before allocating them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum NodeRef<'a> {
+    Atom(&'a [u8]),
+    List(&'a [usize]),
+}
+
+impl Node {
+    fn as_node_ref(&self) -> NodeRef<'_> {
+        match self {
+            Node::Atom(a) => NodeRef::Atom(a),
+            Node::List(v) => NodeRef::List(v),
+        }
+    }
+}
+
+/// Collapses structurally equal subtrees of a forest into shared nodes
+/// - every distinct subtree is stored exactly once
+/// - children always have smaller indices than their parents
+#[derive(Debug, Clone, Default)]
+pub struct BSExpDeduper {
+    nodes: Vec<Node>,
+    /// Last node added with each hash, so nodes are only stored in `nodes`
+    by_hash: HashMap<u64, usize>,
+    /// Previous node added with the same hash as each node
+    same_hash: Vec<Option<usize>>,
+    hasher: RandomState,
+}
+
+impl BSExpDeduper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The node pool built so far
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Add a tree to the pool, returning the index of its root node
+    pub fn add(&mut self, tree: &BSExp) -> usize {
+        let mut stack: Vec<(std::slice::Iter<'_, BSExp>, Vec<usize>)> = Vec::new();
+        let mut node = tree;
+        loop {
+            let mut finished = match node {
+                BSExp::Atom(a) => Some(match self.find(NodeRef::Atom(a)) {
+                    Ok(i) => i,
+                    Err(hash) => self.insert(hash, Node::Atom(a.clone())),
+                }),
+                BSExp::List(v) => {
+                    stack.push((v.iter(), Vec::with_capacity(v.len())));
+                    None
+                }
+            };
+            loop {
+                if let Some(i) = finished.take() {
+                    match stack.last_mut() {
+                        Some((_, done)) => done.push(i),
+                        None => return i,
+                    }
+                }
+                let (rest, done) = stack.last_mut().expect("stack holds the current list");
+                match rest.next() {
+                    Some(child) => {
+                        node = child;
+                        break;
+                    }
+                    None => {
+                        let children = mem::take(done);
+                        stack.pop();
+                        finished = Some(match self.find(NodeRef::List(&children)) {
+                            Ok(i) => i,
+                            Err(hash) => self.insert(hash, Node::List(children)),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Consume the deduper, returning the node pool
+    pub fn finish(self) -> Vec<Node> {
+        self.nodes
+    }
+
+    /// Index of `node` in the pool, or its hash to [`Self::insert`] it
+    fn find(&self, node: NodeRef<'_>) -> Result<usize, u64> {
+        let hash = self.hasher.hash_one(node);
+        let mut candidate = self.by_hash.get(&hash).copied();
+        while let Some(i) = candidate {
+            if self.nodes[i].as_node_ref() == node {
+                return Ok(i);
+            }
+            candidate = self.same_hash[i];
+        }
+        Err(hash)
+    }
+
+    fn insert(&mut self, hash: u64, node: Node) -> usize {
+        let i = self.nodes.len();
+        self.nodes.push(node);
+        self.same_hash.push(self.by_hash.insert(hash, i));
+        i
+    }
+}
+
+/// Deduplicate a forest, returning the node pool and the root index of each tree
+pub fn dedup(forest: &[BSExp]) -> (Vec<Node>, Vec<usize>) {
+    let mut deduper = BSExpDeduper::new();
+    let roots = forest.iter().map(|t| deduper.add(t)).collect();
+    (deduper.finish(), roots)
+}
+
+/// A deduplicated forest, shared subtrees being stored once
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dag {
+    /// Node pool, children always before their parents
+    pub nodes: Vec<Node>,
+    /// Index in `nodes` of each tree of the forest
+    pub roots: Vec<usize>,
+}
+
+impl Dag {
+    /// Deduplicate a forest, see [`dedup`]
+    pub fn new(forest: &[BSExp]) -> Self {
+        let (nodes, roots) = dedup(forest);
+        Dag { nodes, roots }
+    }
+
+    /// Fold the DAG bottom-up, visiting every distinct node exactly once
+    /// - `leaf` is called on atoms, `node` on the results of a list's children
+    /// - returns one result per node, indexed like `nodes`
+    pub fn fold<T>(
+        &self,
+        mut leaf: impl FnMut(&[u8]) -> T,
+        mut node: impl FnMut(&[&T]) -> T,
+    ) -> Vec<T> {
+        let mut results: Vec<T> = Vec::with_capacity(self.nodes.len());
+        for n in &self.nodes {
+            let r = match n {
+                Node::Atom(a) => leaf(a),
+                Node::List(v) => node(&v.iter().map(|&c| &results[c]).collect::<Vec<_>>()),
+            };
+            results.push(r);
+        }
+        results
+    }
+
+    /// Display with shared subtrees labeled, see [`SharedDisplay`]
+    pub fn display(&self) -> SharedDisplay<'_> {
+        SharedDisplay::new(&self.nodes, &self.roots)
+    }
+}
+
+/// Atom payload statistics of a tree, see [`BSExp::atom_redundancy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AtomRedundancy {
+    /// Bytes of every atom occurrence
+    pub total_bytes: usize,
+    /// Bytes of every distinct atom, counted once
+    pub unique_bytes: usize,
+}
+
+impl AtomRedundancy {
+    /// Fraction of the atom payload that is repeated, from 0.0 for no
+    /// repetition towards 1.0 for highly repetitive trees
+    pub fn redundancy(&self) -> f64 {
+        if self.total_bytes == 0 {
+            0.0
+        } else {
+            1.0 - self.unique_bytes as f64 / self.total_bytes as f64
+        }
+    }
+}
+
+impl BSExp {
+    /// Compare total and distinct atom bytes, to estimate whether an
+    /// external compressor is worth applying on top of atom dedup
+    pub fn atom_redundancy(&self) -> AtomRedundancy {
+        let mut deduper = BSExpDeduper::new();
+        deduper.add(self);
+        let unique_bytes = deduper
+            .nodes()
+            .iter()
+            .map(|n| match n {
+                Node::Atom(a) => a.len(),
+                Node::List(_) => 0,
+            })
+            .sum();
+        AtomRedundancy {
+            total_bytes: self.total_atom_bytes(),
+            unique_bytes,
+        }
+    }
+}
+
+/// Display of a deduplicated forest that prints shared lists once
+/// - the first occurrence of a list referenced more than once is labeled
+///   `#n=(...)`, later occurrences are written as `#n#`
+/// - labels are numbered from 1 in printing order, shared across roots
+/// - each root is printed on its own line
+#[derive(Debug, Clone, Copy)]
+pub struct SharedDisplay<'a> {
+    nodes: &'a [Node],
+    roots: &'a [usize],
+}
+
+impl<'a> SharedDisplay<'a> {
+    pub fn new(nodes: &'a [Node], roots: &'a [usize]) -> Self {
+        SharedDisplay { nodes, roots }
+    }
+
+    fn fmt_node(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        i: usize,
+        refs: &[usize],
+        labels: &mut [Option<usize>],
+        next: &mut usize,
+    ) -> fmt::Result {
+        match &self.nodes[i] {
+            Node::Atom(a) => BSExp::fmt_atom(f, a),
+            Node::List(v) => {
+                if refs[i] > 1 {
+                    if let Some(label) = labels[i] {
+                        return write!(f, "#{label}#");
+                    }
+                    labels[i] = Some(*next);
+                    write!(f, "#{next}=")?;
+                    *next += 1;
+                }
+                f.write_str("(")?;
+                for (k, &c) in v.iter().enumerate() {
+                    if k > 0 {
+                        f.write_str(" ")?;
+                    }
+                    self.fmt_node(f, c, refs, labels, next)?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl Display for SharedDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut refs = vec![0; self.nodes.len()];
+        for &r in self.roots {
+            refs[r] += 1;
+        }
+        for node in self.nodes {
+            if let Node::List(v) = node {
+                v.iter().for_each(|&c| refs[c] += 1);
+            }
+        }
+        let mut labels = vec![None; self.nodes.len()];
+        let mut next = 1;
+        for (k, &r) in self.roots.iter().enumerate() {
+            if k > 0 {
+                f.write_str("\n")?;
+            }
+            self.fmt_node(f, r, &refs, &mut labels, &mut next)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_dedup_shared_subtrees() {
+    use crate::bsexp;
+    let (nodes, roots) = dedup(&[bsexp!(["+", ["f", "n"], ["f", "n"]]), bsexp!(["f", "n"])]);
+    assert_eq!(
+        nodes,
+        [
+            Node::Atom(b"+".to_vec()),
+            Node::Atom(b"f".to_vec()),
+            Node::Atom(b"n".to_vec()),
+            Node::List(vec![1, 2]),
+            Node::List(vec![0, 3, 3]),
+        ]
+    );
+    assert_eq!(roots, [4, 3]);
+}
+
+#[test]
+fn test_shared_display() {
+    use crate::bsexp;
+    let (nodes, roots) = dedup(&[
+        bsexp!(["+", ["f", "n"], ["*", ["f", "n"], "n"]]),
+        bsexp!(["g", ["f", "n"]]),
+    ]);
+    assert_eq!(
+        SharedDisplay::new(&nodes, &roots).to_string(),
+        "(+ #1=(f n) (* #1# n))\n(g #1#)"
+    );
+}
+
+#[test]
+fn test_atom_redundancy() {
+    let e = BSExp::list(vec![crate::bsexp!(["define", "x", "value"]); 100]);
+    let r = e.atom_redundancy();
+    assert_eq!(r.total_bytes, 1200);
+    assert_eq!(r.unique_bytes, 12);
+    assert!(r.redundancy() > 0.95);
+    assert_eq!(crate::bsexp!([]).atom_redundancy().redundancy(), 0.0);
+}
+
+#[test]
+fn test_dag_fold_visits_shared_once() {
+    use crate::bsexp;
+    let dag = Dag::new(&[bsexp!(["+", ["f", "n"], ["*", ["f", "n"], ["f", "n"]]])]);
+    let mut visits = Vec::new();
+    let sizes = dag.fold(
+        |_| 1,
+        |children| {
+            visits.push(children.len());
+            1 + children.iter().copied().sum::<usize>()
+        },
+    );
+    assert_eq!(sizes[dag.roots[0]], 13);
+    assert_eq!(visits, [2, 3, 3]);
+    assert_eq!(dag.display().to_string(), "(+ #1=(f n) (* #1# #1#))");
+}
+
+#[test]
+fn test_dedup_stores_nodes_once() {
+    let e = BSExp::list(vec![BSExp::atom("define"); 10_000]);
+    let before = crate::allocations();
+    let mut deduper = BSExpDeduper::new();
+    deduper.add(&e);
+    let allocations = crate::allocations() - before;
+    // buffer growth only, nothing per repeated atom
+    assert!(allocations < 100, "{allocations} allocations");
+    assert_eq!(
+        deduper.finish(),
+        [Node::Atom(b"define".to_vec()), Node::List(vec![0; 10_000])]
+    );
+}
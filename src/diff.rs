@@ -0,0 +1,131 @@
+use crate::BSExp;
+use std::fmt::{self, Display};
+
+/// The first position at which two trees differ, see [`BSExp::diff`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeDiff<'a> {
+    /// Index path from the roots to the differing nodes
+    pub path: Vec<usize>,
+    pub left: &'a BSExp,
+    pub right: &'a BSExp,
+}
+
+impl Display for TreeDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "they differ at path {:?}: `{}` vs `{}`",
+            self.path, self.left, self.right
+        )
+    }
+}
+
+impl BSExp {
+    /// Find the first position, in pre-order, where `self` and `other` differ
+    /// - two atoms differ when their bytes do
+    /// - an atom and a list always differ
+    /// - lists of different lengths differ at the lists themselves,
+    ///   otherwise their children are compared in order
+    pub fn diff<'a>(&'a self, other: &'a BSExp) -> Option<TreeDiff<'a>> {
+        // entries are (length of the parent path, index in the parent,
+        // left, right), the path of each pair being rebuilt in `path`
+        let mut stack = vec![(0, None, self, other)];
+        let mut path = Vec::new();
+        while let Some((depth, index, left, right)) = stack.pop() {
+            path.truncate(depth);
+            path.extend(index);
+            match (left, right) {
+                (BSExp::Atom(l), BSExp::Atom(r)) if l == r => {}
+                (BSExp::List(l), BSExp::List(r)) if l.len() == r.len() => {
+                    stack.extend(
+                        l.iter()
+                            .zip(r)
+                            .enumerate()
+                            .rev()
+                            .map(|(i, (l, r))| (path.len(), Some(i), l, r)),
+                    );
+                }
+                _ => return Some(TreeDiff { path, left, right }),
+            }
+        }
+        None
+    }
+}
+
+impl BSExp {
+    /// Structural equality comparing atoms case-insensitively for ASCII,
+    /// non-ASCII bytes must match exactly
+    pub fn eq_ignoring_case(&self, other: &BSExp) -> bool {
+        self.eq_by(other, <[u8]>::eq_ignore_ascii_case)
+    }
+
+    /// Structural equality comparing atoms with `atom_eq`
+    pub fn eq_by(&self, other: &BSExp, atom_eq: impl Fn(&[u8], &[u8]) -> bool) -> bool {
+        let mut stack = vec![(self, other)];
+        while let Some(pair) = stack.pop() {
+            match pair {
+                (BSExp::Atom(l), BSExp::Atom(r)) if atom_eq(l, r) => {}
+                (BSExp::List(l), BSExp::List(r)) if l.len() == r.len() => {
+                    stack.extend(l.iter().zip(r));
+                }
+                _ => return false,
+            }
+        }
+        true
+    }
+}
+
+#[test]
+fn test_diff_nested() {
+    use crate::bsexp;
+    let a = bsexp!(["define", ["f", "n"], [["a", "b"], "c"]]);
+    let b = bsexp!(["define", ["f", "n"], [["x", "b"], "d"]]);
+    let d = a.diff(&b).unwrap();
+    assert_eq!(d.path, [2, 0, 0]);
+    assert_eq!(d.to_string(), "they differ at path [2, 0, 0]: `a` vs `x`");
+
+    assert_eq!(a.diff(&a.clone()), None);
+    let c = bsexp!(["define", ["f"], "c"]);
+    assert_eq!(
+        a.diff(&c).unwrap().to_string(),
+        "they differ at path [1]: `(f n)` vs `(f)`"
+    );
+    assert!(bsexp!("a").diff(&bsexp!("b")).unwrap().path.is_empty());
+
+    let deep = crate::deep_chain(100_000);
+    let mut other = crate::deep_chain(100_000);
+    *other.get_path_mut(&vec![0; 100_000]).unwrap() = bsexp!("y");
+    let d = deep.diff(&other).unwrap();
+    assert_eq!(
+        (d.path.len(), d.left, d.right),
+        (100_000, &bsexp!("x"), &bsexp!("y"))
+    );
+    assert_eq!(deep.diff(&deep), None);
+    [deep, other].into_iter().for_each(crate::drop_deep);
+}
+
+#[test]
+fn test_eq_ignoring_case() {
+    use crate::bsexp;
+    let a = bsexp!(["DEFINE", ["Fib", "n"], "Ä"]);
+    assert!(a.eq_ignoring_case(&bsexp!(["define", ["fib", "N"], "Ä"])));
+    assert!(!a.eq_ignoring_case(&bsexp!(["define", ["fib", "n"], "ä"])));
+    assert!(!a.eq_ignoring_case(&bsexp!(["define", ["fib", "n"]])));
+    assert!(!bsexp!("a").eq_ignoring_case(&bsexp!(["a"])));
+}
+
+#[test]
+fn test_eq_by() {
+    use crate::bsexp;
+    let trim = |a: &[u8]| a.trim_ascii_end().to_vec();
+    let a = bsexp!(["define ", ["f", "n  "], "x"]);
+    assert!(
+        a.eq_by(&bsexp!(["define", ["f ", "n"], "x"]), |l, r| trim(l)
+            == trim(r))
+    );
+    assert!(
+        !a.eq_by(&bsexp!(["define", ["f", " n"], "x"]), |l, r| trim(l)
+            == trim(r))
+    );
+    assert!(!a.eq_by(&bsexp!(["define", "f", "x"]), |_, _| true));
+}
@@ -0,0 +1,62 @@
+use std::fmt::{self, Display};
+
+/// Errors reported by the checked operations on [`BSExp`](crate::BSExp)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BseError {
+    /// A list did not have the expected number of elements
+    ArityMismatch { expected: usize, got: usize },
+    /// A list operation was applied to an atom
+    NotAList,
+    /// An atom was expected, but a list was found
+    NotAnAtom,
+    /// No node exists at the given index path
+    PathNotFound(Vec<usize>),
+    /// The input ended in the middle of a VLI
+    TruncatedVli,
+    /// Bytes that should hold exactly one VLI hold something else
+    InvalidVli,
+    /// A payload does not fit in a 32-bit length prefix
+    FrameTooLarge(usize),
+    /// The input ended before the end of a length-prefixed frame
+    TruncatedFrame,
+    /// A token stream was unbalanced or did not hold exactly one tree
+    MalformedTokens,
+    /// A tree is nested deeper than the given limit
+    DepthLimitExceeded(usize),
+    /// An atom is longer than the allowed maximum
+    AtomTooLong { len: usize, max: usize },
+    /// A size exceeds the given limit in bytes
+    SizeLimitExceeded(usize),
+    /// A JSON object was found where objects are not accepted
+    UnexpectedJsonObject,
+}
+
+impl Display for BseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BseError::ArityMismatch { expected, got } => {
+                write!(f, "expected arity {expected}, got {got}")
+            }
+            BseError::NotAList => f.write_str("expected a list, got an atom"),
+            BseError::NotAnAtom => f.write_str("expected an atom, got a list"),
+            BseError::PathNotFound(path) => write!(f, "no node at path {path:?}"),
+            BseError::TruncatedVli => f.write_str("truncated VLI"),
+            BseError::InvalidVli => f.write_str("invalid VLI"),
+            BseError::FrameTooLarge(len) => {
+                write!(f, "frame of {len} bytes exceeds the 32-bit length prefix")
+            }
+            BseError::TruncatedFrame => f.write_str("truncated frame"),
+            BseError::MalformedTokens => f.write_str("malformed token stream"),
+            BseError::DepthLimitExceeded(max) => write!(f, "depth exceeds the limit of {max}"),
+            BseError::AtomTooLong { len, max } => {
+                write!(f, "atom of {len} bytes exceeds the limit of {max}")
+            }
+            BseError::SizeLimitExceeded(max) => {
+                write!(f, "size exceeds the limit of {max} bytes")
+            }
+            BseError::UnexpectedJsonObject => f.write_str("unexpected JSON object"),
+        }
+    }
+}
+
+impl std::error::Error for BseError {}
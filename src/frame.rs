@@ -0,0 +1,40 @@
+use crate::BseError;
+
+/// Append `payload` to `buf` behind a big-endian `u32` length prefix,
+/// for consumers that cannot read VLI lengths
+pub fn write_framed_be32(buf: &mut Vec<u8>, payload: &[u8]) -> Result<(), BseError> {
+    let len = u32::try_from(payload.len()).map_err(|_| BseError::FrameTooLarge(payload.len()))?;
+    buf.extend_from_slice(&len.to_be_bytes());
+    buf.extend_from_slice(payload);
+    Ok(())
+}
+
+/// Read one frame written by [`write_framed_be32`],
+/// returning the payload and the bytes after it
+pub fn read_framed_be32(buf: &[u8]) -> Result<(&[u8], &[u8]), BseError> {
+    let (len, rest) = buf
+        .split_first_chunk::<4>()
+        .ok_or(BseError::TruncatedFrame)?;
+    let len = u32::from_be_bytes(*len) as usize;
+    if rest.len() < len {
+        return Err(BseError::TruncatedFrame);
+    }
+    Ok(rest.split_at(len))
+}
+
+#[test]
+fn test_framed_be32_round_trip() {
+    let mut buf = Vec::new();
+    write_framed_be32(&mut buf, b"define").unwrap();
+    write_framed_be32(&mut buf, b"").unwrap();
+    assert_eq!(&buf[0..4], &[0, 0, 0, 6]);
+
+    let (first, rest) = read_framed_be32(&buf).unwrap();
+    assert_eq!(first, b"define");
+    let (second, rest) = read_framed_be32(rest).unwrap();
+    assert_eq!(second, b"");
+    assert!(rest.is_empty());
+
+    assert_eq!(read_framed_be32(&buf[..8]), Err(BseError::TruncatedFrame));
+    assert_eq!(read_framed_be32(&buf[..3]), Err(BseError::TruncatedFrame));
+}
@@ -0,0 +1,83 @@
+use crate::{BSExp, BseError};
+use serde_json::Value;
+
+/// How JSON objects are treated when converting to [`BSExp`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonObjects {
+    /// Objects are rejected with [`BseError::UnexpectedJsonObject`]
+    #[default]
+    Reject,
+    /// Objects become association lists `((key value) ...)`
+    Alist,
+}
+
+impl BSExp {
+    /// Convert a JSON value into a BSExp
+    /// - Arrays become lists
+    /// - Strings become atoms of their UTF-8 bytes
+    /// - Numbers and booleans become atoms of their textual JSON form,
+    ///   e.g. `42` becomes the atom `b"42"`, not a VLI encoded integer
+    /// - `null` becomes the empty list
+    /// - Objects are handled according to `objects`
+    pub fn from_json(value: Value, objects: JsonObjects) -> Result<Self, BseError> {
+        Ok(match value {
+            Value::Null => BSExp::List(vec![]),
+            Value::Bool(b) => BSExp::atom(b.to_string()),
+            Value::Number(n) => BSExp::atom(n.to_string()),
+            Value::String(s) => BSExp::atom(s),
+            Value::Array(v) => BSExp::List(
+                v.into_iter()
+                    .map(|x| BSExp::from_json(x, objects))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(m) => match objects {
+                JsonObjects::Reject => return Err(BseError::UnexpectedJsonObject),
+                JsonObjects::Alist => BSExp::List(
+                    m.into_iter()
+                        .map(|(k, v)| {
+                            Ok(BSExp::list(vec![
+                                BSExp::atom(k),
+                                BSExp::from_json(v, objects)?,
+                            ]))
+                        })
+                        .collect::<Result<_, _>>()?,
+                ),
+            },
+        })
+    }
+}
+
+/// Converts with [`JsonObjects::Reject`], see [`BSExp::from_json`]
+impl TryFrom<Value> for BSExp {
+    type Error = BseError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        BSExp::from_json(value, JsonObjects::Reject)
+    }
+}
+
+#[test]
+fn test_json_nested_arrays() {
+    let v: Value = serde_json::from_str(r#"["define", ["f", "n"], [1, 2.5, true, null]]"#).unwrap();
+    assert_eq!(
+        BSExp::try_from(v),
+        Ok(crate::bsexp!([
+            "define",
+            ["f", "n"],
+            ["1", "2.5", "true", []]
+        ]))
+    );
+}
+
+#[test]
+fn test_json_object() {
+    let v: Value = serde_json::from_str(r#"[{"name": "fib", "args": ["n"]}]"#).unwrap();
+    assert_eq!(
+        BSExp::try_from(v.clone()),
+        Err(BseError::UnexpectedJsonObject)
+    );
+    assert_eq!(
+        BSExp::from_json(v, JsonObjects::Alist).map(|x| x.to_string()),
+        Ok("(((args (n)) (name fib)))".to_string())
+    );
+}
@@ -1,11 +1,35 @@
 // pub mod serialization;
+#[cfg(feature = "bytes")]
+pub mod bytes_exp;
+pub mod columnar;
+pub mod dag;
+pub mod diff;
+pub mod error;
+pub mod frame;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod prelude;
+pub mod shared;
+pub mod token;
+mod transform;
+pub mod validate;
 pub mod vli;
 
+pub use error::BseError;
+use vli::VLI;
+
 use std::fmt::{self, Display};
 
 /// Binary S-Expression
 /// - Atoms are in bytes
 /// - Lists are represented as vector
+/// - List operations applied to an atom never panic, except `Extend`,
+///   and leave the atom unchanged:
+///   - queries return `None` or nothing
+///   - mutations return `Err(BseError::NotAList)`, except the ones that
+///     already report a missing child: [`BSExp::replace_path`] returns
+///     `Err(BseError::PathNotFound)` and [`BSExp::take_child`] returns
+///     `None`, as for an index past the end of a list
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BSExp {
     Atom(Vec<u8>),
@@ -22,6 +46,264 @@ impl BSExp {
     pub fn list<T: Into<Vec<BSExp>>>(value: T) -> Self {
         BSExp::List(value.into())
     }
+
+    /// Create an empty list with room for `n` elements
+    pub fn list_with_capacity(n: usize) -> Self {
+        BSExp::List(Vec::with_capacity(n))
+    }
+
+    /// Append an element to a list
+    pub fn push(&mut self, item: BSExp) -> Result<(), BseError> {
+        match self {
+            BSExp::List(v) => {
+                v.push(item);
+                Ok(())
+            }
+            BSExp::Atom(_) => Err(BseError::NotAList),
+        }
+    }
+
+    /// Create a new atom taking ownership of `bytes`, without copying
+    pub fn atom_owned(bytes: Vec<u8>) -> Self {
+        BSExp::Atom(bytes)
+    }
+
+    /// Create a new atom from a copy of any byte-like value, e.g. `&str`,
+    /// `String`, `Vec<u8>`, `&[u8]` or `&[u8; N]`
+    pub fn atom_bytes<T: AsRef<[u8]>>(value: T) -> Self {
+        BSExp::Atom(value.as_ref().to_vec())
+    }
+
+    /// Create a new atom holding a copy of arbitrary binary data,
+    /// with no assumption that it is UTF-8
+    pub fn from_raw_bytes(bytes: &[u8]) -> Self {
+        BSExp::Atom(bytes.to_vec())
+    }
+
+    /// Create a new atom holding the VLI encoding of `value`
+    pub fn atom_vli(value: u64) -> Self {
+        let mut v = Vec::new();
+        vli::push_vli(&mut v, value);
+        BSExp::Atom(v)
+    }
+
+    /// Create a new UTF-8 atom from the `Display` output of `value`,
+    /// e.g. `atom_display(42)` is the textual atom `b"42"`
+    pub fn atom_display<T: Display>(value: T) -> Self {
+        BSExp::Atom(value.to_string().into_bytes())
+    }
+
+    /// Whether this is an atom
+    ///
+    /// ```
+    /// use bsexp::bsexp;
+    ///
+    /// let mut e = bsexp!(["f", ["g"], "n"]);
+    /// assert!(!e.is_atom());
+    /// if let bsexp::BSExp::List(v) = &mut e {
+    ///     v.retain(|x| x.is_atom());
+    /// }
+    /// assert_eq!(e, bsexp!(["f", "n"]));
+    /// ```
+    pub fn is_atom(&self) -> bool {
+        matches!(self, BSExp::Atom(_))
+    }
+
+    /// Whether this is a list
+    ///
+    /// ```
+    /// use bsexp::bsexp;
+    ///
+    /// let e = bsexp!(["f", ["g"], []]);
+    /// assert!(e.is_list());
+    /// assert_eq!(e.into_list().unwrap().iter().filter(|x| x.is_list()).count(), 2);
+    /// ```
+    pub fn is_list(&self) -> bool {
+        matches!(self, BSExp::List(_))
+    }
+
+    /// Number of elements of a list, `None` for atoms
+    pub fn arity(&self) -> Option<usize> {
+        match self {
+            BSExp::Atom(_) => None,
+            BSExp::List(v) => Some(v.len()),
+        }
+    }
+
+    /// Check that this is a list of exactly `n` elements
+    pub fn expect_arity(&self, n: usize) -> Result<(), BseError> {
+        match self.arity() {
+            None => Err(BseError::NotAList),
+            Some(got) if got != n => Err(BseError::ArityMismatch { expected: n, got }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Iterate over the elements of a list, empty for atoms
+    /// - the iterator is a slice iterator, so `len()` and `rev()` are cheap
+    pub fn children(&self) -> std::slice::Iter<'_, BSExp> {
+        match self {
+            BSExp::List(v) => v.iter(),
+            BSExp::Atom(_) => [].iter(),
+        }
+    }
+
+    /// First element of a list (car), `None` for atoms and the empty list
+    pub fn first(&self) -> Option<&BSExp> {
+        match self {
+            BSExp::List(v) => v.first(),
+            BSExp::Atom(_) => None,
+        }
+    }
+
+    /// Elements of a list after the first (cdr), `None` for atoms and the
+    /// empty list
+    pub fn rest(&self) -> Option<&[BSExp]> {
+        match self {
+            BSExp::List(v) => v.split_first().map(|(_, rest)| rest),
+            BSExp::Atom(_) => None,
+        }
+    }
+
+    /// Remove the element at `index` from a list and return it, shifting
+    /// the following elements left, `None` for atoms and out of range
+    /// indices, which leave `self` unchanged
+    pub fn take_child(&mut self, index: usize) -> Option<BSExp> {
+        match self {
+            BSExp::List(v) if index < v.len() => Some(v.remove(index)),
+            _ => None,
+        }
+    }
+
+    /// Borrow the bytes of an atom, `None` for lists
+    pub fn bytes(&self) -> Option<&[u8]> {
+        match self {
+            BSExp::Atom(v) => Some(v),
+            BSExp::List(_) => None,
+        }
+    }
+
+    /// Mutably borrow the backing storage of an atom for in-place edits,
+    /// `None` for lists
+    pub fn bytes_mut(&mut self) -> Option<&mut Vec<u8>> {
+        match self {
+            BSExp::Atom(v) => Some(v),
+            BSExp::List(_) => None,
+        }
+    }
+
+    /// Take the bytes out of an atom, or give back a list unchanged
+    pub fn into_atom(self) -> Result<Vec<u8>, BSExp> {
+        match self {
+            BSExp::Atom(v) => Ok(v),
+            e => Err(e),
+        }
+    }
+
+    /// Take the elements out of a list, or give back an atom unchanged
+    pub fn into_list(self) -> Result<Vec<BSExp>, BSExp> {
+        match self {
+            BSExp::List(v) => Ok(v),
+            e => Err(e),
+        }
+    }
+
+    /// Decode a list of VLI atoms, such as built by [`BSExp::atom_vli`]
+    /// - every child must be an atom holding exactly one VLI
+    pub fn as_u64_list(&self) -> Result<Vec<u64>, BseError> {
+        let BSExp::List(v) = self else {
+            return Err(BseError::NotAList);
+        };
+        v.iter()
+            .map(|x| {
+                let a = x.bytes().ok_or(BseError::NotAnAtom)?;
+                let mut it = a.iter();
+                let (value, _) =
+                    u64::read_vli_counted(|| it.next().copied().ok_or(BseError::TruncatedVli))?;
+                match it.as_slice() {
+                    [] => Ok(value),
+                    _ => Err(BseError::InvalidVli),
+                }
+            })
+            .collect()
+    }
+
+    /// Interpret a list of two-element lists as an association list
+    /// - fails with the error of [`BSExp::expect_arity`] on the first child
+    ///   that is not a two-element list
+    pub fn as_list_of_pairs(&self) -> Result<Vec<(&BSExp, &BSExp)>, BseError> {
+        let BSExp::List(v) = self else {
+            return Err(BseError::NotAList);
+        };
+        v.iter()
+            .map(|x| {
+                x.expect_arity(2)?;
+                let pair = x.children().as_slice();
+                Ok((&pair[0], &pair[1]))
+            })
+            .collect()
+    }
+
+    /// Follow a path of child indices from this node
+    /// - the empty path refers to the node itself
+    pub fn get_path(&self, path: &[usize]) -> Option<&BSExp> {
+        path.iter().try_fold(self, |node, &i| match node {
+            BSExp::List(v) => v.get(i),
+            BSExp::Atom(_) => None,
+        })
+    }
+
+    /// Mutable version of [`BSExp::get_path`]
+    pub fn get_path_mut(&mut self, path: &[usize]) -> Option<&mut BSExp> {
+        path.iter().try_fold(self, |node, &i| match node {
+            BSExp::List(v) => v.get_mut(i),
+            BSExp::Atom(_) => None,
+        })
+    }
+
+    /// Replace the node at `path` with `new`
+    pub fn replace_path(&mut self, path: &[usize], new: BSExp) -> Result<(), BseError> {
+        let node = self
+            .get_path_mut(path)
+            .ok_or_else(|| BseError::PathNotFound(path.to_vec()))?;
+        *node = new;
+        Ok(())
+    }
+
+    /// Total length of the bytes of every atom in the tree,
+    /// excluding any structural overhead
+    pub fn total_atom_bytes(&self) -> usize {
+        self.preorder()
+            .filter_map(BSExp::bytes)
+            .map(<[u8]>::len)
+            .sum()
+    }
+
+    /// Number of nodes at each depth, the root having depth 0
+    pub fn depth_histogram(&self) -> Vec<usize> {
+        let mut histogram = Vec::new();
+        let mut stack = vec![(self, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            if histogram.len() <= depth {
+                histogram.resize(depth + 1, 0);
+            }
+            histogram[depth] += 1;
+            stack.extend(node.children().map(|x| (x, depth + 1)));
+        }
+        histogram
+    }
+
+    /// Iterate over this node and all of its descendants in pre-order
+    pub(crate) fn preorder(&self) -> impl Iterator<Item = &BSExp> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            if let BSExp::List(v) = node {
+                stack.extend(v.iter().rev());
+            }
+            Some(node)
+        })
+    }
 }
 
 impl From<&str> for BSExp {
@@ -36,12 +318,55 @@ impl From<String> for BSExp {
     }
 }
 
+impl From<&String> for BSExp {
+    fn from(s: &String) -> Self {
+        BSExp::Atom(s.as_bytes().to_vec())
+    }
+}
+
+impl From<Box<str>> for BSExp {
+    fn from(s: Box<str>) -> Self {
+        BSExp::Atom(s.into_boxed_bytes().into_vec())
+    }
+}
+
+impl From<Vec<u8>> for BSExp {
+    fn from(v: Vec<u8>) -> Self {
+        BSExp::Atom(v)
+    }
+}
+
+impl From<Vec<BSExp>> for BSExp {
+    fn from(v: Vec<BSExp>) -> Self {
+        BSExp::List(v)
+    }
+}
+
+/// Append items to a list
+///
+/// # Panics
+///
+/// Panics if `self` is an atom
+impl Extend<BSExp> for BSExp {
+    fn extend<T: IntoIterator<Item = BSExp>>(&mut self, iter: T) {
+        match self {
+            BSExp::List(v) => v.extend(iter),
+            BSExp::Atom(_) => panic!("cannot extend an atom"),
+        }
+    }
+}
+
+/// Build a BSExp from a nested list literal
+/// - `[ ... ]` becomes a list, anything else a leaf
+/// - a leaf is a single token tree, wrap longer expressions in parentheses
+/// - a leaf may be any `T: Into<BSExp>`, e.g. `&str`, `String`, `&String`,
+///   `Box<str>` or `Vec<u8>`, and the kinds can be mixed freely
 #[macro_export]
 macro_rules! bsexp {
     // Match a list expression: [ ... ]
     ( [ $( $elem:tt ),* ] ) => {
         $crate::BSExp::list(vec![
-            $( bsexp!($elem) ),*
+            $( $crate::bsexp!($elem) ),*
         ])
     };
 
@@ -51,23 +376,149 @@ macro_rules! bsexp {
     };
 }
 
+/// Fallible version of [`bsexp!`]
+/// - leaves are converted with `TryFrom`, the first failure is returned
+/// - leaf errors are converted with `Into` into the error type of the
+///   whole expression, so infallible leaves need `From<Infallible>`
+#[macro_export]
+macro_rules! try_bsexp {
+    // Match a list expression: [ ... ]
+    ( [ $( $elem:tt ),* ] ) => {
+        ::core::iter::IntoIterator::into_iter([
+            $( $crate::try_bsexp!($elem) ),*
+        ])
+        .collect::<::core::result::Result<::std::vec::Vec<$crate::BSExp>, _>>()
+        .map($crate::BSExp::List)
+    };
+
+    // Match a single atom expression
+    ( $atom:expr ) => {
+        <$crate::BSExp as ::core::convert::TryFrom<_>>::try_from($atom)
+            .map_err(::core::convert::Into::into)
+    };
+}
+
+/// Layout options for the pretty-printer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayStyle {
+    /// Lists whose single-line form is shorter than this stay on one line
+    /// - the indentation in front of a list is not counted, so the
+    ///   result does not depend on `indent`
+    pub width: usize,
+    /// Keep a leading sublist on the line of the opening paren when it
+    /// fits on one line, instead of moving it to a line of its own
+    pub hug_first_list: bool,
+    /// What is written for each level of nesting on a wrapped line
+    pub indent: Indent,
+    /// Always wrap a list that directly contains an atom longer than this
+    /// many bytes, even if its single-line form is short enough
+    /// - every list around such a list is wrapped too
+    pub force_break_atom_len: Option<usize>,
+    /// Align the elements of a wrapped list under its first argument,
+    /// as in `(foo arg1\n     arg2)`, instead of indenting by level
+    /// - alignment always uses spaces, `indent` and `hug_first_list`
+    ///   are not used in this mode
+    /// - `width` is a right margin in this mode: a list stays inline only
+    ///   when the whole line it ends on is shorter, columns being counted
+    ///   in characters
+    pub hanging: bool,
+}
+
+/// Indentation unit of the pretty-printer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Indent {
+    /// A number of spaces per level
+    Spaces(usize),
+    /// One tab character per level, whatever width the viewer gives it
+    Tabs,
+}
+
+impl Default for DisplayStyle {
+    fn default() -> Self {
+        DisplayStyle {
+            width: 60,
+            hug_first_list: false,
+            indent: Indent::Spaces(1),
+            force_break_atom_len: None,
+            hanging: false,
+        }
+    }
+}
+
+/// Pretty-printed view of a [`BSExp`] with an explicit [`DisplayStyle`]
+/// - `{:#}` on a BSExp is the same as `pretty(DisplayStyle::default())`
+#[derive(Debug, Clone, Copy)]
+pub struct Pretty<'a> {
+    exp: &'a BSExp,
+    style: DisplayStyle,
+}
+
+impl BSExp {
+    /// Pretty-print this BSExp with the given style
+    pub fn pretty(&self, style: DisplayStyle) -> Pretty<'_> {
+        Pretty { exp: self, style }
+    }
+}
+
+impl Display for Pretty<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.style.hanging {
+            self.exp.fmt_hanging(f, 0, 0, &self.style)
+        } else {
+            self.exp.fmt_pretty(f, 0, &self.style)
+        }
+    }
+}
+
+/// Compact single-line view of a [`BSExp`], see [`BSExp::compact`]
+#[derive(Debug, Clone, Copy)]
+pub struct Compact<'a>(&'a BSExp);
+
+impl BSExp {
+    /// Single-line display that only separates adjacent atoms with a
+    /// space, e.g. `(f(g x)y)` instead of `(f (g x) y)`
+    /// - an empty atom keeps the space on both sides, as in `( ())`, so
+    ///   that it stays distinct from `(())`
+    /// - there is no parser yet, so the round trip back to the same tree
+    ///   is not tested
+    pub fn compact(&self) -> Compact<'_> {
+        Compact(self)
+    }
+}
+
+impl Display for Compact<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            BSExp::Atom(item) => BSExp::fmt_atom(f, item),
+            BSExp::List(items) => {
+                let empty = |x: &BSExp| x.bytes().is_some_and(<[u8]>::is_empty);
+                f.write_str("(")?;
+                for (i, x) in items.iter().enumerate() {
+                    if i > 0 {
+                        let prev = &items[i - 1];
+                        if (prev.is_atom() && x.is_atom()) || empty(prev) || empty(x) {
+                            f.write_str(" ")?;
+                        }
+                    }
+                    write!(f, "{}", x.compact())?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
 impl Display for BSExp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if f.alternate() {
-            self.fmt_pretty(f, 0)
+            let style = DisplayStyle {
+                width: f.width().unwrap_or(DisplayStyle::default().width),
+                ..DisplayStyle::default()
+            };
+            self.fmt_pretty(f, 0, &style)
         } else {
             match self {
-                BSExp::Atom(item) => match String::from_utf8(item.clone()) {
-                    Ok(s) => f.write_str(s.as_str()),
-                    Err(_) => write!(
-                        f,
-                        "{}",
-                        item.iter()
-                            .map(|b| b.to_string())
-                            .collect::<Vec<_>>()
-                            .join(" ")
-                    ),
-                },
+                BSExp::Atom(item) => BSExp::fmt_atom(f, item),
                 BSExp::List(items) => write!(
                     f,
                     "({})",
@@ -82,43 +533,253 @@ impl Display for BSExp {
     }
 }
 impl BSExp {
-    fn indention(f: &mut fmt::Formatter<'_>, indent: usize) -> Result<(), std::fmt::Error> {
-        (0..indent).map(|_| f.write_str(" ")).collect()
+    /// Write the bytes of an atom, as text when they are valid UTF-8 and
+    /// as space separated decimal bytes otherwise
+    fn fmt_atom(f: &mut fmt::Formatter<'_>, item: &[u8]) -> fmt::Result {
+        match std::str::from_utf8(item) {
+            Ok(s) => BSExp::write_escaped(f, s),
+            Err(_) => write!(
+                f,
+                "{}",
+                item.iter()
+                    .map(|b| b.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            ),
+        }
+    }
+
+    /// Write a UTF-8 atom, escaping `\` and ASCII control characters
+    /// - `\t`, `\n`, `\r` and `\0` use their short forms
+    /// - other control characters are written as `\xNN`
+    fn write_escaped(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            if !c.is_ascii_control() && c != '\\' {
+                continue;
+            }
+            f.write_str(&s[start..i])?;
+            start = i + 1;
+            match c {
+                '\\' => f.write_str("\\\\")?,
+                '\t' => f.write_str("\\t")?,
+                '\n' => f.write_str("\\n")?,
+                '\r' => f.write_str("\\r")?,
+                '\0' => f.write_str("\\0")?,
+                c => write!(f, "\\x{:02x}", c as u32)?,
+            }
+        }
+        f.write_str(&s[start..])
+    }
+
+    /// Single-line `Display` output, cut to at most `max_chars` characters
+    /// for log lines
+    /// - a tree that fits is returned in full
+    /// - otherwise the output stops before the first atom or paren that
+    ///   does not fit, followed by `...` and the parens still open, e.g.
+    ///   `(define (f n) ...)`
+    /// - atoms are never cut in the middle; `...` alone is returned when
+    ///   nothing fits, even if `max_chars` is below 3
+    pub fn display_truncated(&self, max_chars: usize) -> String {
+        // a tree that fits is shallow enough for the recursive Display
+        if self.inline_display_columns() <= max_chars {
+            return self.to_string();
+        }
+        let mut out = String::new();
+        let mut len = 0;
+        let mut after_open = true;
+        let mut stack = vec![std::slice::from_ref(self).iter()];
+        loop {
+            let depth = stack.len() - 1;
+            let next = stack.last_mut().expect("stack holds the root").next();
+            // an empty list is one piece, so that it is never cut to `(...)`
+            let (piece_len, next_depth, opens) = match next {
+                Some(BSExp::List(v)) if v.is_empty() => (2, depth, false),
+                Some(BSExp::List(_)) => (1, depth + 1, true),
+                Some(BSExp::Atom(a)) => (BSExp::atom_len(a, |_| 1), depth, false),
+                None if depth == 0 => break,
+                None => (1, depth - 1, false),
+            };
+            let sep = usize::from(!after_open && next.is_some());
+            let ellipsis_sep = usize::from(!opens);
+            if len + sep + piece_len + ellipsis_sep + 3 + next_depth > max_chars {
+                break;
+            }
+            if sep == 1 {
+                out.push(' ');
+            }
+            match next {
+                Some(BSExp::List(v)) if v.is_empty() => out.push_str("()"),
+                Some(BSExp::List(v)) => {
+                    out.push('(');
+                    stack.push(v.iter());
+                }
+                Some(atom) => out.push_str(&atom.to_string()),
+                None => {
+                    out.push(')');
+                    stack.pop();
+                }
+            }
+            len += sep + piece_len;
+            after_open = opens;
+        }
+        if !after_open {
+            out.push(' ');
+        }
+        out.push_str("...");
+        out.extend(std::iter::repeat_n(')', stack.len() - 1));
+        out
+    }
+
+    /// Length in bytes of the single-line `Display` output, computed
+    /// without formatting, e.g. for custom layout engines
+    /// - always equal to `self.to_string().len()`
+    pub fn inline_display_len(&self) -> usize {
+        self.inline_len(char::len_utf8)
+    }
+
+    /// Number of characters of the single-line `Display` output, the
+    /// column count of terminals and editors for most text
+    fn inline_display_columns(&self) -> usize {
+        self.inline_len(|_| 1)
+    }
+
+    /// Length of the single-line `Display` output, `char_len` giving the
+    /// length of every unescaped character of a UTF-8 atom
+    fn inline_len(&self, char_len: fn(char) -> usize) -> usize {
+        self.preorder()
+            .map(|node| match node {
+                BSExp::Atom(item) => BSExp::atom_len(item, char_len),
+                BSExp::List(v) => 2 + v.len().saturating_sub(1),
+            })
+            .sum()
+    }
+
+    /// Length of the `Display` output of an atom, see [`BSExp::inline_len`]
+    fn atom_len(item: &[u8], char_len: fn(char) -> usize) -> usize {
+        match std::str::from_utf8(item) {
+            Ok(s) => s
+                .chars()
+                .map(|c| match c {
+                    '\\' | '\t' | '\n' | '\r' | '\0' => 2,
+                    c if c.is_ascii_control() => 4,
+                    c => char_len(c),
+                })
+                .sum(),
+            Err(_) => {
+                let digits = |b: &u8| match b {
+                    0..=9 => 1,
+                    10..=99 => 2,
+                    _ => 3,
+                };
+                item.iter().map(digits).sum::<usize>() + item.len().saturating_sub(1)
+            }
+        }
+    }
+
+    /// Whether the pretty-printer keeps this node on a single line
+    fn fits_inline(&self, style: &DisplayStyle) -> bool {
+        self.inline_display_len() < style.width && !self.has_forced_break(style)
+    }
+
+    /// Whether this subtree holds an atom longer than
+    /// [`DisplayStyle::force_break_atom_len`]
+    fn has_forced_break(&self, style: &DisplayStyle) -> bool {
+        style.force_break_atom_len.is_some_and(|max| {
+            self.preorder()
+                .any(|x| x.bytes().is_some_and(|a| a.len() > max))
+        })
+    }
+
+    fn indention(
+        f: &mut fmt::Formatter<'_>,
+        indent: usize,
+        style: &DisplayStyle,
+    ) -> Result<(), std::fmt::Error> {
+        match style.indent {
+            Indent::Spaces(n) => (0..indent * n).try_for_each(|_| f.write_str(" ")),
+            Indent::Tabs => (0..indent).try_for_each(|_| f.write_str("\t")),
+        }
+    }
+
+    /// Hanging-indent layout, the cursor being at `column` already and
+    /// `closing` parens of enclosing lists following right after this node
+    /// - a list stays inline when the line it ends, parens included, is
+    ///   shorter than the width, columns being counted in characters
+    fn fmt_hanging(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        column: usize,
+        closing: usize,
+        style: &DisplayStyle,
+    ) -> fmt::Result {
+        let BSExp::List(v) = self else {
+            return write!(f, "{self}");
+        };
+        if column + self.inline_display_columns() + closing < style.width
+            && !self.has_forced_break(style)
+        {
+            return write!(f, "{self}");
+        }
+        f.write_str("(")?;
+        let last = v.len().saturating_sub(1);
+        let closing_after = |k| if k == last { closing + 1 } else { 0 };
+        let mut it = v.iter().enumerate();
+        let mut align = column + 1;
+        if let Some(op @ BSExp::Atom(_)) = v.first() {
+            write!(f, "{op}")?;
+            it.next();
+            align += op.inline_display_columns() + 1;
+            if let Some((k, x)) = it.next() {
+                f.write_str(" ")?;
+                x.fmt_hanging(f, align, closing_after(k), style)?;
+            }
+        } else if let Some((k, x)) = it.next() {
+            x.fmt_hanging(f, align, closing_after(k), style)?;
+        }
+        for (k, x) in it {
+            f.write_str("\n")?;
+            (0..align).try_for_each(|_| f.write_str(" "))?;
+            x.fmt_hanging(f, align, closing_after(k), style)?;
+        }
+        f.write_str(")")
     }
 
-    fn fmt_pretty(&self, f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+    fn fmt_pretty(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        indent: usize,
+        style: &DisplayStyle,
+    ) -> fmt::Result {
         match self {
             BSExp::Atom(_) => {
-                BSExp::indention(f, indent)?;
+                BSExp::indention(f, indent, style)?;
                 write!(f, "{self}")
             }
 
             BSExp::List(v) => {
-                let mut buf = String::new();
-                use std::fmt::Write as _;
-                write!(&mut buf, "{self}")?;
-
-                if buf.len() < f.width().unwrap_or(60) {
-                    BSExp::indention(f, indent)?;
-                    f.write_str(&buf)
+                if self.fits_inline(style) {
+                    BSExp::indention(f, indent, style)?;
+                    write!(f, "{self}")
                 } else {
-                    BSExp::indention(f, indent)?;
+                    BSExp::indention(f, indent, style)?;
                     f.write_str("(")?;
 
                     let mut it = v.iter();
                     if let Some(x) = it.next() {
                         match x {
                             BSExp::Atom(_) => write!(f, "{x}")?,
+                            _ if style.hug_first_list && x.fits_inline(style) => write!(f, "{x}")?,
                             _ => {
                                 f.write_str("\n")?;
-                                x.fmt_pretty(f, indent + 1)?
+                                x.fmt_pretty(f, indent + 1, style)?
                             }
                         }
                     }
 
                     for x in it {
                         f.write_str("\n")?;
-                        x.fmt_pretty(f, indent + 1)?;
+                        x.fmt_pretty(f, indent + 1, style)?;
                     }
 
                     f.write_str(")")
@@ -173,3 +834,556 @@ fn test_bsexp_format_pretty() {
         "(define\n (fibonacci n)\n (define\n  (fib-iter a b count)\n  (if (= count 0) a (fib-iter b (+ a b) (- count 1))))\n (fib-iter 0 1 n))"
     );
 }
+
+#[test]
+fn test_bsexp_arity() {
+    let e = bsexp!(["if", "c", "a"]);
+    assert_eq!(e.arity(), Some(3));
+    assert_eq!(bsexp!("a").arity(), None);
+    assert_eq!(e.expect_arity(3), Ok(()));
+    assert_eq!(
+        bsexp!(["if", "c"]).expect_arity(3).unwrap_err().to_string(),
+        "expected arity 3, got 2"
+    );
+    assert_eq!(bsexp!("if").expect_arity(3), Err(BseError::NotAList));
+}
+
+#[test]
+fn test_bsexp_bytes_mut() {
+    let mut e = bsexp!("fib");
+    let ptr = e.bytes().unwrap().as_ptr();
+    e.bytes_mut().unwrap()[0] = b'F';
+    assert_eq!(e.bytes(), Some(&b"Fib"[..]));
+    assert_eq!(e.bytes().unwrap().as_ptr(), ptr);
+    assert_eq!(bsexp!(["fib"]).bytes_mut(), None);
+}
+
+#[test]
+fn test_bsexp_format_control_chars() {
+    let e = bsexp!(["print", "a\tb", "c\\d\x07"]);
+    assert_eq!(format!("{e}"), "(print a\\tb c\\\\d\\x07)");
+}
+
+#[test]
+fn test_bsexp_format_atom_borrowed() {
+    use std::fmt::Write;
+    // counts the writes instead of storing them
+    struct Discard(usize);
+    impl Write for Discard {
+        fn write_str(&mut self, _: &str) -> fmt::Result {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+
+    let big = "x".repeat(1 << 16);
+    let atom = BSExp::atom(big.as_str());
+    let mut sink = Discard(0);
+    let before = allocations();
+    write!(sink, "{atom}").unwrap();
+    assert_eq!(allocations(), before);
+    assert_eq!(sink.0, 1);
+
+    let mut sink = Discard(0);
+    write!(sink, "{}", BSExp::atom(format!("{big}\t{big}"))).unwrap();
+    assert_eq!(sink.0, 3);
+
+    assert_eq!(atom.to_string(), big);
+    assert_eq!(BSExp::Atom(vec![0xff, 0, 7]).to_string(), "255 0 7");
+}
+
+#[test]
+fn test_bsexp_path() {
+    let mut e = bsexp!(["define", ["f", "n"], ["if", ["=", "n", "0"], "1", "n"]]);
+    assert_eq!(e.get_path(&[]), Some(&e.clone()));
+    assert_eq!(e.get_path(&[2, 1, 2]), Some(&bsexp!("0")));
+    assert_eq!(e.get_path(&[2, 1, 2, 0]), None);
+    assert_eq!(e.get_path(&[5]), None);
+
+    e.replace_path(&[2, 1, 2], bsexp!(["zero"])).unwrap();
+    assert_eq!(e.to_string(), "(define (f n) (if (= n (zero)) 1 n))");
+    assert_eq!(
+        e.replace_path(&[1, 9], bsexp!("x")),
+        Err(BseError::PathNotFound(vec![1, 9]))
+    );
+}
+
+/// Global allocator of the unit tests, counting the allocations of each
+/// thread so that tests can check an operation does not copy its input
+#[cfg(test)]
+struct CountingAlloc;
+
+#[cfg(test)]
+thread_local! {
+    static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+#[cfg(test)]
+unsafe impl std::alloc::GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        unsafe { std::alloc::System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+        unsafe { std::alloc::System.dealloc(ptr, layout) }
+    }
+}
+
+#[cfg(test)]
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Number of allocations made so far by the current thread
+#[cfg(test)]
+pub(crate) fn allocations() -> usize {
+    ALLOCATIONS.with(std::cell::Cell::get)
+}
+
+/// The atom `x` wrapped in `depth` nested singleton lists
+#[cfg(test)]
+pub(crate) fn deep_chain(depth: usize) -> BSExp {
+    (0..depth).fold(BSExp::atom("x"), |e, _| BSExp::List(vec![e]))
+}
+
+/// Drop a tree with an explicit stack, the derived drop glue being
+/// recursive and overflowing on the trees of [`deep_chain`]
+#[cfg(test)]
+pub(crate) fn drop_deep(e: BSExp) {
+    let mut stack = vec![e];
+    while let Some(node) = stack.pop() {
+        if let BSExp::List(v) = node {
+            stack.extend(v);
+        }
+    }
+}
+
+/// The fibonacci definition used throughout the tests
+#[cfg(test)]
+pub(crate) fn fib_example() -> BSExp {
+    bsexp!([
+        "define",
+        ["fibonacci", "n"],
+        [
+            "define",
+            ["fib-iter", "a", "b", "count"],
+            [
+                "if",
+                ["=", "count", "0"],
+                "a",
+                ["fib-iter", "b", ["+", "a", "b"], ["-", "count", "1"]]
+            ]
+        ],
+        ["fib-iter", "0", "1", "n"]
+    ])
+}
+
+#[test]
+fn test_bsexp_total_atom_bytes() {
+    assert_eq!(fib_example().total_atom_bytes(), 77);
+    assert_eq!(bsexp!([[], ["ab"]]).total_atom_bytes(), 2);
+}
+
+#[test]
+fn test_try_bsexp_macro() {
+    #[derive(Debug, PartialEq)]
+    struct Odd(u32);
+    impl From<std::convert::Infallible> for Odd {
+        fn from(x: std::convert::Infallible) -> Self {
+            match x {}
+        }
+    }
+    struct Even(u32);
+    impl TryFrom<Even> for BSExp {
+        type Error = Odd;
+        fn try_from(Even(n): Even) -> Result<Self, Odd> {
+            if n % 2 == 0 {
+                Ok(BSExp::atom(n.to_string()))
+            } else {
+                Err(Odd(n))
+            }
+        }
+    }
+
+    let ok: Result<BSExp, Odd> = try_bsexp!(["half", [(Even(4)), "x"], []]);
+    assert_eq!(ok, Ok(bsexp!(["half", ["4", "x"], []])));
+    let err: Result<BSExp, Odd> = try_bsexp!(["half", [(Even(4)), (Even(7))]]);
+    assert_eq!(err, Err(Odd(7)));
+}
+
+#[test]
+fn test_bsexp_format_hug_first_list() {
+    let e = bsexp!([
+        ["lambda", ["x"], "x"],
+        ["fibonacci", "some-long-argument"],
+        ["fibonacci", "another-long-argument"]
+    ]);
+    let style = DisplayStyle {
+        width: 40,
+        ..DisplayStyle::default()
+    };
+    assert_eq!(format!("{e:#40}"), format!("{}", e.pretty(style)));
+    assert_eq!(
+        format!("{}", e.pretty(style)),
+        "(\n (lambda (x) x)\n (fibonacci some-long-argument)\n (fibonacci another-long-argument))"
+    );
+    let style = DisplayStyle {
+        hug_first_list: true,
+        ..style
+    };
+    assert_eq!(
+        format!("{}", e.pretty(style)),
+        "((lambda (x) x)\n (fibonacci some-long-argument)\n (fibonacci another-long-argument))"
+    );
+}
+
+#[test]
+fn test_bsexp_atom_display() {
+    assert_eq!(BSExp::atom_display(42), BSExp::Atom(b"42".to_vec()));
+    assert_eq!(BSExp::atom_display(-2.5f64), BSExp::Atom(b"-2.5".to_vec()));
+}
+
+#[test]
+fn test_bsexp_inline_display_len() {
+    for e in [
+        fib_example(),
+        bsexp!([]),
+        bsexp!([[], "", ["a\tb", "\x01\\"]]),
+        bsexp!(["λ", ["x"]]),
+        BSExp::list(vec![
+            BSExp::Atom(vec![0xff, 0, 42]),
+            BSExp::Atom(vec![0x80]),
+        ]),
+    ] {
+        assert_eq!(e.inline_display_len(), e.to_string().len(), "{e}");
+    }
+}
+
+#[test]
+fn test_bsexp_extend() {
+    let mut e = bsexp!(["+", "a"]);
+    e.extend(["b", "c"].map(BSExp::atom));
+    assert_eq!(e, bsexp!(["+", "a", "b", "c"]));
+    assert!(std::panic::catch_unwind(|| bsexp!("a").extend([bsexp!("b")])).is_err());
+}
+
+#[test]
+fn test_bsexp_into_atom_list() {
+    assert_eq!(bsexp!("a").into_atom(), Ok(b"a".to_vec()));
+    assert_eq!(bsexp!("a").into_list(), Err(bsexp!("a")));
+    assert_eq!(bsexp!(["a"]).into_list(), Ok(vec![bsexp!("a")]));
+    assert_eq!(bsexp!(["a"]).into_atom(), Err(bsexp!(["a"])));
+}
+
+#[test]
+fn test_bsexp_from_string_refs() {
+    let name = String::from("fib");
+    assert_eq!(BSExp::atom(&name), bsexp!("fib"));
+    let boxed: Box<str> = "fib-iter".into();
+    assert_eq!(BSExp::atom(boxed), bsexp!("fib-iter"));
+}
+
+#[test]
+fn test_bsexp_from_raw_bytes() {
+    let e = BSExp::from_raw_bytes(&[0xff, 0xfe, 0]);
+    assert_eq!(e.bytes(), Some(&[0xff, 0xfe, 0][..]));
+    assert_eq!(e.to_string(), "255 254 0");
+}
+
+#[test]
+fn test_bsexp_from_vec() {
+    let e: BSExp = vec![bsexp!("f"), bsexp!("n")].into();
+    assert_eq!(e, bsexp!(["f", "n"]));
+}
+
+#[test]
+fn test_bsexp_format_pretty_empty_list() {
+    let e = bsexp!([
+        "cond",
+        [["null?", "some-long-list-name"], []],
+        ["else", ["cons", "head", []]],
+        []
+    ]);
+    assert_eq!(
+        format!("{e:#36}"),
+        "(cond\n ((null? some-long-list-name) ())\n (else (cons head ()))\n ())"
+    );
+    assert_eq!(format!("{:#1}", bsexp!([])), "()");
+    assert_eq!(format!("{:#1}", bsexp!([[]])), "(\n ())");
+}
+
+#[test]
+fn test_bsexp_format_pretty_tabs() {
+    let style = DisplayStyle {
+        indent: Indent::Tabs,
+        ..DisplayStyle::default()
+    };
+    assert_eq!(
+        fib_example().pretty(style).to_string(),
+        "(define\n\t(fibonacci n)\n\t(define\n\t\t(fib-iter a b count)\n\t\t(if (= count 0) a (fib-iter b (+ a b) (- count 1))))\n\t(fib-iter 0 1 n))"
+    );
+    let style = DisplayStyle {
+        indent: Indent::Spaces(2),
+        ..DisplayStyle::default()
+    };
+    assert_eq!(
+        bsexp!([
+            "define",
+            ["fibonacci", "n"],
+            ["some-rather-long-body", "with", "arguments"]
+        ])
+        .pretty(style)
+        .to_string(),
+        "(define\n  (fibonacci n)\n  (some-rather-long-body with arguments))"
+    );
+}
+
+#[test]
+fn test_bsexp_first_rest() {
+    let e = bsexp!(["+", "a", ["b"]]);
+    assert_eq!(e.first(), Some(&bsexp!("+")));
+    assert_eq!(e.rest(), Some(&[bsexp!("a"), bsexp!(["b"])][..]));
+    assert_eq!(bsexp!(["a"]).rest(), Some(&[][..]));
+    assert_eq!(bsexp!([]).first(), None);
+    assert_eq!(bsexp!("a").first(), None);
+    assert_eq!(bsexp!("a").rest(), None);
+}
+
+#[test]
+fn test_bsexp_children() {
+    let e = bsexp!(["f", "a", ["b"]]);
+    assert_eq!(e.children().len(), 3);
+    assert_eq!(
+        e.children()
+            .rev()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>(),
+        ["(b)", "a", "f"]
+    );
+    assert_eq!(bsexp!("f").children().len(), 0);
+    assert_eq!(bsexp!("f").children().next_back(), None);
+}
+
+#[test]
+fn test_bsexp_format_force_break_atom_len() {
+    let e = bsexp!(["print", "a-rather-long-message", ["f", "x"]]);
+    assert_eq!(format!("{e:#}"), "(print a-rather-long-message (f x))");
+    let style = DisplayStyle {
+        force_break_atom_len: Some(16),
+        ..DisplayStyle::default()
+    };
+    assert_eq!(
+        e.pretty(style).to_string(),
+        "(print\n a-rather-long-message\n (f x))"
+    );
+    let nested = bsexp!(["do", ["print", "a-rather-long-message", ["f", "x"]]]);
+    assert_eq!(
+        nested.pretty(style).to_string(),
+        "(do\n (print\n  a-rather-long-message\n  (f x)))"
+    );
+}
+
+#[test]
+fn test_bsexp_macro_mixed_leaves() {
+    let name = String::from("fib");
+    let e = bsexp!(["define", (name.clone()), [(&name), (vec![0xffu8, 1])]]);
+    assert_eq!(
+        e,
+        BSExp::list(vec![
+            BSExp::Atom(b"define".to_vec()),
+            BSExp::Atom(b"fib".to_vec()),
+            BSExp::list(vec![
+                BSExp::Atom(b"fib".to_vec()),
+                BSExp::Atom(vec![0xff, 1])
+            ]),
+        ])
+    );
+}
+
+#[test]
+fn test_bsexp_as_u64_list() {
+    let values = [0, 1, 300, 1 << 56, u64::MAX];
+    let e = BSExp::list(values.map(BSExp::atom_vli));
+    assert_eq!(e.as_u64_list(), Ok(values.to_vec()));
+
+    let nested = BSExp::list(vec![BSExp::atom_vli(1), bsexp!([])]);
+    assert_eq!(nested.as_u64_list(), Err(BseError::NotAnAtom));
+    assert_eq!(bsexp!([""]).as_u64_list(), Err(BseError::TruncatedVli));
+    assert_eq!(
+        BSExp::list(vec![BSExp::Atom(vec![1, 2])]).as_u64_list(),
+        Err(BseError::InvalidVli)
+    );
+    assert_eq!(BSExp::atom_vli(1).as_u64_list(), Err(BseError::NotAList));
+}
+
+#[test]
+fn test_bsexp_list_with_capacity() {
+    let mut e = BSExp::list_with_capacity(3);
+    let BSExp::List(v) = &e else { unreachable!() };
+    assert!(v.is_empty() && v.capacity() >= 3);
+    for x in ["a", "b", "c"] {
+        e.push(bsexp!(x)).unwrap();
+    }
+    assert_eq!(e, bsexp!(["a", "b", "c"]));
+    assert_eq!(bsexp!("a").push(bsexp!("b")), Err(BseError::NotAList));
+}
+
+#[test]
+fn test_bsexp_format_hanging() {
+    let style = DisplayStyle {
+        hanging: true,
+        ..DisplayStyle::default()
+    };
+    assert_eq!(
+        fib_example().pretty(style).to_string(),
+        "(define (fibonacci n)\n        (define (fib-iter a b count)\n                (if (= count 0)\n                    a\n                    (fib-iter b (+ a b) (- count 1))))\n        (fib-iter 0 1 n))"
+    );
+    for width in [40, 50, 60, 80] {
+        let style = DisplayStyle { width, ..style };
+        let out = fib_example().pretty(style).to_string();
+        assert!(out.lines().all(|l| l.chars().count() < width), "{out}");
+    }
+    let style = DisplayStyle { width: 30, ..style };
+    assert_eq!(
+        bsexp!([
+            "if",
+            ["=", "count", "0"],
+            "a",
+            ["fib-iter", "b", ["+", "a", "b"], ["-", "count", "1"]]
+        ])
+        .pretty(style)
+        .to_string(),
+        "(if (= count 0)\n    a\n    (fib-iter b\n              (+ a b)\n              (- count 1)))"
+    );
+    let style = DisplayStyle { width: 12, ..style };
+    assert_eq!(
+        bsexp!(["λλ", "aaaa", "bbbb"]).pretty(style).to_string(),
+        "(λλ aaaa\n    bbbb)"
+    );
+}
+
+#[test]
+fn test_bsexp_list_ops_on_atom() {
+    let mut a = bsexp!("atom");
+    assert_eq!(a.arity(), None);
+    assert_eq!(a.children().len(), 0);
+    assert_eq!(a.first(), None);
+    assert_eq!(a.rest(), None);
+    assert_eq!(a.get_path(&[0]), None);
+    assert_eq!(a.expect_arity(0), Err(BseError::NotAList));
+    assert_eq!(a.as_u64_list(), Err(BseError::NotAList));
+    assert_eq!(a.push(bsexp!("x")), Err(BseError::NotAList));
+    assert_eq!(a.retain_mut(|_| true), Err(BseError::NotAList));
+    assert_eq!(
+        a.replace_path(&[0], bsexp!("x")),
+        Err(BseError::PathNotFound(vec![0]))
+    );
+    assert_eq!(a.take_child(0), None);
+    assert_eq!(a, bsexp!("atom"));
+}
+
+#[test]
+fn test_bsexp_format_compact() {
+    assert_eq!(
+        fib_example().compact().to_string(),
+        "(define(fibonacci n)(define(fib-iter a b count)(if(= count 0)a(fib-iter b(+ a b)(- count 1))))(fib-iter 0 1 n))"
+    );
+    assert_eq!(bsexp!([[], [], "a"]).compact().to_string(), "(()()a)");
+    assert_eq!(bsexp!("a").compact().to_string(), "a");
+    assert_eq!(bsexp!(["", []]).compact().to_string(), "( ())");
+    assert_eq!(bsexp!([[]]).compact().to_string(), "(())");
+    assert_eq!(bsexp!([[], "", []]).compact().to_string(), "(()  ())");
+}
+
+#[test]
+fn test_bsexp_atom_owned_no_copy() {
+    let bytes = b"fib-iter".to_vec();
+    let ptr = bytes.as_ptr();
+    assert_eq!(BSExp::atom_owned(bytes).bytes().unwrap().as_ptr(), ptr);
+
+    let s = String::from("fib-iter");
+    let ptr = s.as_ptr();
+    assert_eq!(BSExp::atom(s).bytes().unwrap().as_ptr(), ptr);
+}
+
+#[test]
+fn test_bsexp_depth_histogram() {
+    assert_eq!(fib_example().depth_histogram(), [1, 4, 9, 8, 7, 6]);
+    assert_eq!(bsexp!("a").depth_histogram(), [1]);
+}
+
+#[test]
+fn test_bsexp_atom_bytes() {
+    let expected = BSExp::Atom(b"fib".to_vec());
+    assert_eq!(BSExp::atom_bytes("fib"), expected);
+    assert_eq!(BSExp::atom_bytes(String::from("fib")), expected);
+    assert_eq!(BSExp::atom_bytes(vec![b'f', b'i', b'b']), expected);
+    assert_eq!(BSExp::atom_bytes(&b"fib"[..]), expected);
+    assert_eq!(BSExp::atom_bytes(b"fib"), expected);
+    assert_eq!(BSExp::atom_bytes([0xffu8, 0]), BSExp::Atom(vec![0xff, 0]));
+}
+
+#[test]
+fn test_bsexp_as_list_of_pairs() {
+    let alist = bsexp!([["name", "fib"], ["args", ["n"]]]);
+    assert_eq!(
+        alist.as_list_of_pairs(),
+        Ok(vec![
+            (&bsexp!("name"), &bsexp!("fib")),
+            (&bsexp!("args"), &bsexp!(["n"]))
+        ])
+    );
+    assert_eq!(
+        bsexp!([["name", "fib"], ["args"]]).as_list_of_pairs(),
+        Err(BseError::ArityMismatch {
+            expected: 2,
+            got: 1
+        })
+    );
+    assert_eq!(
+        bsexp!([["name", "fib"], "args"]).as_list_of_pairs(),
+        Err(BseError::NotAList)
+    );
+}
+
+#[test]
+fn test_take_child() {
+    let mut e = bsexp!(["define", ["f", "n"], ["+", "n", ["g", "1"]]]);
+    let body = e.get_path_mut(&[2]).unwrap();
+    assert_eq!(body.take_child(2), Some(bsexp!(["g", "1"])));
+    assert_eq!(body.take_child(2), None);
+    assert_eq!(e, bsexp!(["define", ["f", "n"], ["+", "n"]]));
+    assert_eq!(e.take_child(0), Some(bsexp!("define")));
+    assert_eq!(bsexp!("x").take_child(0), None);
+}
+
+#[test]
+fn test_display_truncated() {
+    let fib = fib_example();
+    let full = fib.to_string();
+    assert_eq!(fib.display_truncated(full.len()), full);
+    for (max, expected) in [
+        (0, "..."),
+        (4, "..."),
+        (5, "(...)"),
+        (12, "(define ...)"),
+        (24, "(define (fibonacci ...))"),
+        (26, "(define (fibonacci n) ...)"),
+        (40, "(define (fibonacci n) (define (...)))"),
+    ] {
+        let cut = fib.display_truncated(max);
+        assert_eq!(cut, expected, "max_chars = {max}");
+        assert_eq!(cut.matches('(').count(), cut.matches(')').count());
+        assert!(cut.chars().count() <= max.max(3));
+    }
+    assert_eq!(bsexp!("long-atom").display_truncated(5), "...");
+    let deep = deep_chain(100_000);
+    assert_eq!(deep.display_truncated(20), "((((((((...))))))))");
+    assert_eq!(deep_chain(9).display_truncated(20), "(((((((((x)))))))))");
+    drop_deep(deep);
+    let empty = bsexp!(["x", ["y", ["z", []]], "tail"]);
+    assert_eq!(empty.display_truncated(17), "(x (y (z ...)))");
+    assert_eq!(empty.display_truncated(18), "(x (y (z ())) ...)");
+    assert_eq!(bsexp!([[], "long-atom"]).display_truncated(10), "(() ...)");
+    assert_eq!(bsexp!(["中文", "x"]).display_truncated(6), "(中文 x)");
+}
@@ -0,0 +1,26 @@
+//! The commonly used items of the crate, for glob import
+//!
+//! ```
+//! use bsexp::prelude::*;
+//! use std::convert::Infallible;
+//!
+//! let e: BSExp = bsexp!(["f", ["g", "x"], "y"]);
+//! let t: Result<BSExp, Infallible> = try_bsexp!(["f", (vec![b'x'])]);
+//! assert_eq!(t, Ok(bsexp!(["f", "x"])));
+//! assert_eq!(e.expect_arity(2), Err(BseError::ArityMismatch { expected: 2, got: 3 }));
+//!
+//! let style = DisplayStyle { width: 0, indent: Indent::Tabs, ..Default::default() };
+//! let _: Pretty<'_> = e.pretty(style);
+//! let _: Compact<'_> = e.compact();
+//! assert_eq!(e.compact().to_string(), "(f(g x)y)");
+//!
+//! let (bytes, len) = 300u64.to_vli_bytes();
+//! assert_eq!(&bytes[..len], [0xac, 0x02]);
+//!
+//! assert_eq!(e.validate_with(&Limits::default()), Ok(()));
+//! assert_eq!(Dag::new(&[e.clone(), e]).roots, [5, 5]);
+//! ```
+pub use crate::dag::Dag;
+pub use crate::validate::Limits;
+pub use crate::vli::VLI;
+pub use crate::{BSExp, BseError, Compact, DisplayStyle, Indent, Pretty, bsexp, try_bsexp};
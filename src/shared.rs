@@ -0,0 +1,86 @@
+use crate::BSExp;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+/// Binary S-Expression whose atoms share reference counted storage
+/// - equal atoms produced by [`BSExp::dedup_atoms`] point to one buffer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SharedBSExp {
+    Atom(Rc<[u8]>),
+    List(Vec<SharedBSExp>),
+}
+
+impl BSExp {
+    /// Convert into a [`SharedBSExp`], storing each distinct atom once
+    pub fn dedup_atoms(&self) -> SharedBSExp {
+        let mut pool: HashSet<Rc<[u8]>> = HashSet::new();
+        self.fold(
+            |a| match pool.get(a) {
+                Some(rc) => SharedBSExp::Atom(rc.clone()),
+                None => {
+                    let rc: Rc<[u8]> = a.into();
+                    pool.insert(rc.clone());
+                    SharedBSExp::Atom(rc)
+                }
+            },
+            SharedBSExp::List,
+        )
+    }
+}
+
+impl From<&SharedBSExp> for BSExp {
+    fn from(e: &SharedBSExp) -> Self {
+        match e {
+            SharedBSExp::Atom(a) => BSExp::Atom(a.to_vec()),
+            SharedBSExp::List(v) => BSExp::List(v.iter().map(Into::into).collect()),
+        }
+    }
+}
+
+#[test]
+fn test_dedup_atoms_shares_buffers() {
+    let fib = crate::fib_example();
+    let shared = fib.dedup_atoms();
+    assert_eq!(BSExp::from(&shared), fib);
+
+    let mut stack = vec![&shared];
+    let mut atoms = 0;
+    let mut buffers: Vec<*const [u8]> = Vec::new();
+    while let Some(node) = stack.pop() {
+        match node {
+            SharedBSExp::Atom(a) => {
+                atoms += 1;
+                buffers.push(Rc::as_ptr(a));
+            }
+            SharedBSExp::List(v) => stack.extend(v),
+        }
+    }
+    buffers.sort();
+    buffers.dedup();
+    assert_eq!(atoms, 25);
+    assert_eq!(buffers.len(), 13);
+}
+
+#[test]
+fn test_dedup_atoms_allocations() {
+    let words = [
+        "define", "lambda", "if", "let", "x", "y", "z", "n", "+", "-",
+    ];
+    let e = BSExp::list(
+        (0..1000)
+            .map(|i| BSExp::atom(words[i % words.len()]))
+            .collect::<Vec<_>>(),
+    );
+
+    let before = crate::allocations();
+    let copy = e.clone();
+    assert_eq!(crate::allocations() - before, 1000 + 1);
+
+    let before = crate::allocations();
+    let shared = e.dedup_atoms();
+    let allocations = crate::allocations() - before;
+    // one buffer per distinct atom, the rest being the list and the
+    // growth of the pool and the fold stack
+    assert!(allocations < words.len() + 16, "{allocations} allocations");
+    assert_eq!(BSExp::from(&shared), copy);
+}
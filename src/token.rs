@@ -0,0 +1,89 @@
+use crate::{BSExp, BseError};
+
+/// A flat token of a [`BSExp`], see [`BSExp::tokenize`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Token<'a> {
+    Open,
+    Close,
+    Atom(&'a [u8]),
+}
+
+impl BSExp {
+    /// Flatten the tree into a token stream, lists becoming `Open`,
+    /// their elements, then `Close`
+    pub fn tokenize(&self) -> Vec<Token<'_>> {
+        let mut tokens = Vec::new();
+        let mut stack = vec![std::slice::from_ref(self).iter()];
+        while let Some(it) = stack.last_mut() {
+            match it.next() {
+                Some(BSExp::Atom(a)) => tokens.push(Token::Atom(a)),
+                Some(BSExp::List(v)) => {
+                    tokens.push(Token::Open);
+                    stack.push(v.iter());
+                }
+                None => {
+                    stack.pop();
+                    if !stack.is_empty() {
+                        tokens.push(Token::Close);
+                    }
+                }
+            }
+        }
+        tokens
+    }
+
+    /// Rebuild a tree from a token stream produced by [`BSExp::tokenize`]
+    /// - the stream must hold exactly one balanced tree
+    pub fn from_tokens<'a>(tokens: impl IntoIterator<Item = Token<'a>>) -> Result<Self, BseError> {
+        let mut stack: Vec<Vec<BSExp>> = Vec::new();
+        let mut root = None;
+        for token in tokens {
+            if root.is_some() {
+                return Err(BseError::MalformedTokens);
+            }
+            let done = match token {
+                Token::Open => {
+                    stack.push(Vec::new());
+                    continue;
+                }
+                Token::Atom(a) => BSExp::Atom(a.to_vec()),
+                Token::Close => BSExp::List(stack.pop().ok_or(BseError::MalformedTokens)?),
+            };
+            match stack.last_mut() {
+                Some(v) => v.push(done),
+                None => root = Some(done),
+            }
+        }
+        root.ok_or(BseError::MalformedTokens)
+    }
+}
+
+#[test]
+fn test_tokenize_round_trip() {
+    let fib = crate::fib_example();
+    let tokens = fib.tokenize();
+    assert_eq!(
+        &tokens[..3],
+        &[Token::Open, Token::Atom(b"define"), Token::Open]
+    );
+    assert_eq!(tokens.last(), Some(&Token::Close));
+    assert_eq!(BSExp::from_tokens(tokens), Ok(fib));
+
+    let atom = crate::bsexp!("a");
+    assert_eq!(atom.tokenize(), [Token::Atom(b"a")]);
+    assert_eq!(BSExp::from_tokens(atom.tokenize()), Ok(atom));
+
+    use Token::*;
+    for bad in [
+        &[Open][..],
+        &[Close],
+        &[Open, Close, Close],
+        &[Atom(b"a"), Atom(b"b")],
+        &[],
+    ] {
+        assert_eq!(
+            BSExp::from_tokens(bad.iter().copied()),
+            Err(BseError::MalformedTokens)
+        );
+    }
+}
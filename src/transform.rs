@@ -0,0 +1,409 @@
+use crate::{BSExp, BseError};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::mem;
+
+impl BSExp {
+    /// Apply `f` to every node of the tree in post-order
+    /// - children are visited before their parent, left to right
+    /// - nodes produced by `f` are not visited again
+    /// - an explicit stack is used, so deep trees do not overflow
+    pub fn walk_mut(&mut self, mut f: impl FnMut(&mut BSExp)) {
+        let mut stack: Vec<(Vec<BSExp>, std::vec::IntoIter<BSExp>)> = Vec::new();
+        let mut node = mem::replace(self, BSExp::List(vec![]));
+        loop {
+            let mut finished = match node {
+                BSExp::List(v) => {
+                    stack.push((Vec::with_capacity(v.len()), v.into_iter()));
+                    None
+                }
+                atom => Some(atom),
+            };
+            loop {
+                if let Some(mut x) = finished.take() {
+                    f(&mut x);
+                    match stack.last_mut() {
+                        Some((done, _)) => done.push(x),
+                        None => {
+                            *self = x;
+                            return;
+                        }
+                    }
+                }
+                let (done, rest) = stack.last_mut().expect("stack holds the current list");
+                match rest.next() {
+                    Some(child) => {
+                        node = child;
+                        break;
+                    }
+                    None => {
+                        finished = Some(BSExp::List(mem::take(done)));
+                        stack.pop();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rebuild the tree bottom-up, passing the already rebuilt children of
+    /// every list to `f`
+    /// - lists are visited in post-order, left to right
+    /// - atoms are copied unchanged
+    pub fn map_lists(&self, mut f: impl FnMut(Vec<BSExp>) -> Vec<BSExp>) -> BSExp {
+        self.fold(|a| BSExp::Atom(a.to_vec()), |v| BSExp::List(f(v)))
+    }
+
+    /// Remove empty lists from the children of every list, bottom-up
+    /// - a list left empty by the pruning is removed from its parent too
+    /// - the root itself is kept, so a tree of only lists prunes to `()`
+    pub fn prune_empty(&self) -> BSExp {
+        self.fold(
+            |a| BSExp::Atom(a.to_vec()),
+            |v| {
+                BSExp::List(
+                    v.into_iter()
+                        .filter(|x| !matches!(x, BSExp::List(c) if c.is_empty()))
+                        .collect(),
+                )
+            },
+        )
+    }
+
+    /// Replace every atom found as a key of `map` by the mapped bytes,
+    /// leaving the other atoms untouched
+    pub fn rename_symbols(&self, map: &HashMap<Vec<u8>, Vec<u8>>) -> BSExp {
+        self.fold(
+            |a| BSExp::Atom(map.get(a).map_or_else(|| a.to_vec(), Clone::clone)),
+            BSExp::List,
+        )
+    }
+
+    /// Rebuild the tree with every atom replaced by the result of `f`,
+    /// stopping at the first error
+    /// - atoms are visited in pre-order, left to right
+    pub fn try_map_atoms<E>(
+        &self,
+        mut f: impl FnMut(&[u8]) -> Result<Vec<u8>, E>,
+    ) -> Result<BSExp, E> {
+        self.try_fold(|a| f(a).map(BSExp::Atom), |v| Ok(BSExp::List(v)))
+    }
+
+    /// Rebuild the tree with every atom replaced by the elements returned
+    /// by `f`, spliced in place among its siblings
+    /// - an empty result removes the atom from its list
+    /// - atoms are visited in pre-order, left to right, and the produced
+    ///   elements are not visited again
+    /// - a root atom that expands to exactly one element becomes that
+    ///   element, any other expansion of a root atom is wrapped in a list
+    pub fn flat_map_atoms(&self, mut f: impl FnMut(&[u8]) -> Vec<BSExp>) -> BSExp {
+        let mut expanded = self.fold(&mut f, |v| {
+            vec![BSExp::List(v.into_iter().flatten().collect())]
+        });
+        match expanded.len() {
+            1 => expanded.pop().expect("one element"),
+            _ => BSExp::List(expanded),
+        }
+    }
+
+    /// Fold the tree bottom-up with an explicit stack
+    /// - `leaf` is called on every atom, `node` on the folded children of
+    ///   every list, in post-order, left to right
+    pub(crate) fn fold<T>(
+        &self,
+        mut leaf: impl FnMut(&[u8]) -> T,
+        mut node: impl FnMut(Vec<T>) -> T,
+    ) -> T {
+        match self.try_fold(|a| Ok::<_, Infallible>(leaf(a)), |v| Ok(node(v))) {
+            Ok(x) => x,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Fallible version of [`BSExp::fold`], returning the first error as
+    /// soon as `leaf` or `node` fails, without visiting the rest of the tree
+    pub(crate) fn try_fold<T, E>(
+        &self,
+        mut leaf: impl FnMut(&[u8]) -> Result<T, E>,
+        mut node: impl FnMut(Vec<T>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut stack: Vec<(std::slice::Iter<'_, BSExp>, Vec<T>)> = Vec::new();
+        let mut current = self;
+        loop {
+            let mut finished = match current {
+                BSExp::Atom(a) => Some(leaf(a)?),
+                BSExp::List(v) => {
+                    stack.push((v.iter(), Vec::with_capacity(v.len())));
+                    None
+                }
+            };
+            loop {
+                if let Some(x) = finished.take() {
+                    match stack.last_mut() {
+                        Some((_, done)) => done.push(x),
+                        None => return Ok(x),
+                    }
+                }
+                let (rest, done) = stack.last_mut().expect("stack holds the current list");
+                match rest.next() {
+                    Some(child) => {
+                        current = child;
+                        break;
+                    }
+                    None => {
+                        let v = mem::take(done);
+                        stack.pop();
+                        finished = Some(node(v)?);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Keep only the children of a list for which `f` returns `true`,
+    /// letting `f` modify the children it inspects
+    pub fn retain_mut(&mut self, f: impl FnMut(&mut BSExp) -> bool) -> Result<(), BseError> {
+        match self {
+            BSExp::List(v) => {
+                v.retain_mut(f);
+                Ok(())
+            }
+            BSExp::Atom(_) => Err(BseError::NotAList),
+        }
+    }
+
+    /// Bounded preview of the tree for logs and UIs
+    /// - the root has depth 0, its children depth 1 and so on
+    /// - a non-empty list at depth `max_depth`, whose children would be
+    ///   deeper, is replaced by the placeholder atom `...`
+    /// - an explicit stack is used, so deep trees do not overflow
+    pub fn truncate_depth(&self, max_depth: usize) -> BSExp {
+        let mut stack: Vec<(std::slice::Iter<'_, BSExp>, Vec<BSExp>)> = Vec::new();
+        let mut current = self;
+        loop {
+            let mut finished = match current {
+                BSExp::List(v) if !v.is_empty() && stack.len() == max_depth => {
+                    Some(BSExp::atom("..."))
+                }
+                BSExp::List(v) => {
+                    stack.push((v.iter(), Vec::with_capacity(v.len())));
+                    None
+                }
+                atom => Some(atom.clone()),
+            };
+            loop {
+                if let Some(x) = finished.take() {
+                    match stack.last_mut() {
+                        Some((_, done)) => done.push(x),
+                        None => return x,
+                    }
+                }
+                let (rest, done) = stack.last_mut().expect("stack holds the current list");
+                match rest.next() {
+                    Some(child) => {
+                        current = child;
+                        break;
+                    }
+                    None => {
+                        let v = mem::take(done);
+                        stack.pop();
+                        finished = Some(BSExp::List(v));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Shrink the backing vectors of every atom and list to their length
+    pub fn shrink_to_fit(&mut self) {
+        let mut stack = vec![self];
+        while let Some(node) = stack.pop() {
+            match node {
+                BSExp::Atom(v) => v.shrink_to_fit(),
+                BSExp::List(v) => {
+                    v.shrink_to_fit();
+                    stack.extend(v.iter_mut());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_walk_mut_wrap_atoms() {
+    use crate::bsexp;
+    let mut e = bsexp!(["lambda", ["x"], ["+", "x", ["*", "x", "y"]]]);
+    e.walk_mut(|n| {
+        if n.bytes() == Some(b"x") {
+            *n = bsexp!(["ref", "x"]);
+        }
+    });
+    assert_eq!(
+        e.to_string(),
+        "(lambda ((ref x)) (+ (ref x) (* (ref x) y)))"
+    );
+
+    let mut order = Vec::new();
+    bsexp!([["a"], "b"]).walk_mut(|n| order.push(n.to_string()));
+    assert_eq!(order, ["a", "(a)", "b", "((a) b)"]);
+
+    let mut deep = crate::deep_chain(200_000);
+    let mut atoms = 0;
+    deep.walk_mut(|n| {
+        if n.is_atom() {
+            atoms += 1;
+            *n = BSExp::atom("y");
+        }
+    });
+    assert_eq!(atoms, 1);
+    assert_eq!(deep.depth_histogram().len(), 200_001);
+    assert_eq!(deep.get_path(&vec![0; 200_000]), Some(&BSExp::atom("y")));
+    crate::drop_deep(deep);
+}
+
+#[test]
+fn test_shrink_to_fit() {
+    let mut atom = Vec::with_capacity(64);
+    atom.extend_from_slice(b"fib");
+    let mut list = Vec::with_capacity(64);
+    list.push(BSExp::Atom(atom));
+    let mut e = BSExp::List(vec![BSExp::List(list)]);
+    e.shrink_to_fit();
+
+    let BSExp::List(outer) = &e else {
+        unreachable!()
+    };
+    let BSExp::List(inner) = &outer[0] else {
+        unreachable!()
+    };
+    let BSExp::Atom(atom) = &inner[0] else {
+        unreachable!()
+    };
+    assert_eq!(inner.capacity(), inner.len());
+    assert_eq!(atom.capacity(), atom.len());
+}
+
+#[test]
+fn test_map_lists_reverse() {
+    use crate::bsexp;
+    let e = bsexp!(["define", ["f", "n"], ["+", "n", ["g", "1"]]]);
+    let reversed = e.map_lists(|mut v| {
+        v.reverse();
+        v
+    });
+    assert_eq!(reversed.to_string(), "(((1 g) n +) (n f) define)");
+}
+
+#[test]
+fn test_retain_mut() {
+    use crate::bsexp;
+    let mut e = bsexp!(["progn", ["nil-macro"], ["inc", "x"], "y"]);
+    e.retain_mut(|c| match c {
+        BSExp::List(v) if v.len() == 1 => false,
+        BSExp::List(v) => {
+            v[0] = bsexp!("expanded");
+            true
+        }
+        BSExp::Atom(_) => true,
+    })
+    .unwrap();
+    assert_eq!(e, bsexp!(["progn", ["expanded", "x"], "y"]));
+
+    let mut a = bsexp!("atom");
+    assert_eq!(a.retain_mut(|_| false), Err(BseError::NotAList));
+    assert_eq!(a, bsexp!("atom"));
+}
+
+#[test]
+fn test_truncate_depth() {
+    let fib = crate::fib_example();
+    assert_eq!(
+        fib.truncate_depth(2).to_string(),
+        "(define (fibonacci n) (define ... ...) (fib-iter 0 1 n))"
+    );
+    assert_eq!(fib.truncate_depth(0).to_string(), "...");
+    assert_eq!(fib.truncate_depth(10), fib);
+    assert_eq!(crate::bsexp!([[]]).truncate_depth(1), crate::bsexp!([[]]));
+
+    let deep = crate::deep_chain(100_000);
+    let full = deep.truncate_depth(usize::MAX);
+    assert_eq!(full.depth_histogram().len(), 100_001);
+    let cut = deep.truncate_depth(50_000);
+    assert_eq!(cut.depth_histogram().len(), 50_001);
+    assert_eq!(cut.get_path(&vec![0; 50_000]), Some(&BSExp::atom("...")));
+    [deep, full, cut].into_iter().for_each(crate::drop_deep);
+}
+
+#[test]
+fn test_try_map_atoms() {
+    use crate::bsexp;
+    let e = bsexp!(["+", "1", ["*", "2", "x"], "y"]);
+    let mut seen = Vec::new();
+    let r = e.try_map_atoms(|a| {
+        seen.push(a.to_vec());
+        match a {
+            b"x" => Err("unbound x"),
+            a => Ok(a.to_ascii_uppercase()),
+        }
+    });
+    assert_eq!(r, Err("unbound x"));
+    assert_eq!(seen.len(), 5);
+
+    let upper = bsexp!(["f", ["g"]]).try_map_atoms(|a| Ok::<_, ()>(a.to_ascii_uppercase()));
+    assert_eq!(upper, Ok(bsexp!(["F", ["G"]])));
+
+    let mut lists = 0;
+    let r = bsexp!(["+", ["f", "1"], "x", ["g", ["h", "y"]]]).try_fold(
+        |a| if a == b"x" { Err(()) } else { Ok(()) },
+        |_| {
+            lists += 1;
+            Ok(())
+        },
+    );
+    assert_eq!((r, lists), (Err(()), 1));
+}
+
+#[test]
+fn test_prune_empty() {
+    use crate::bsexp;
+    let e = bsexp!(["begin", [], ["f", [], "x"], [[], [[]]], "y"]);
+    assert_eq!(e.prune_empty(), bsexp!(["begin", ["f", "x"], "y"]));
+    assert_eq!(bsexp!([[[]], []]).prune_empty(), bsexp!([]));
+    assert_eq!(bsexp!("a").prune_empty(), bsexp!("a"));
+}
+
+#[test]
+fn test_rename_symbols() {
+    let map = HashMap::from([(b"fib-iter".to_vec(), b"iter".to_vec())]);
+    assert_eq!(
+        crate::fib_example().rename_symbols(&map).to_string(),
+        "(define (fibonacci n) (define (iter a b count) (if (= count 0) a (iter b (+ a b) (- count 1)))) (iter 0 1 n))"
+    );
+}
+
+#[test]
+fn test_flat_map_atoms() {
+    use crate::bsexp;
+    let expand = |a: &[u8]| match a {
+        b"args" => vec![bsexp!("a"), bsexp!("b"), bsexp!(["c", "d"])],
+        b"nothing" => vec![],
+        a => vec![BSExp::from_raw_bytes(a)],
+    };
+    let e = bsexp!(["f", "args", ["g", "args", "nothing"], "x"]);
+    assert_eq!(
+        e.flat_map_atoms(expand),
+        bsexp!(["f", "a", "b", ["c", "d"], ["g", "a", "b", ["c", "d"]], "x"])
+    );
+    assert_eq!(bsexp!("x").flat_map_atoms(expand), bsexp!("x"));
+    assert_eq!(bsexp!("nothing").flat_map_atoms(expand), bsexp!([]));
+    assert_eq!(
+        bsexp!("args").flat_map_atoms(expand),
+        bsexp!(["a", "b", ["c", "d"]])
+    );
+
+    let deep = crate::deep_chain(200_000);
+    let flat = deep.flat_map_atoms(|_| vec![bsexp!("y"), bsexp!("z")]);
+    let histogram = flat.depth_histogram();
+    assert_eq!((histogram.len(), histogram.last()), (200_001, Some(&2)));
+    assert_eq!(flat.get_path(&vec![0; 199_999]), Some(&bsexp!(["y", "z"])));
+    [deep, flat].into_iter().for_each(crate::drop_deep);
+}
@@ -0,0 +1,97 @@
+use crate::{BSExp, BseError};
+
+/// Bounds checked by [`BSExp::validate_with`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum nesting depth, the root having depth 0
+    pub max_depth: usize,
+    /// Maximum length in bytes of a single atom
+    pub max_atom_len: usize,
+    /// Maximum of [`BSExp::total_atom_bytes`]
+    pub max_total_atom_bytes: usize,
+}
+
+/// Only the depth is bounded by default, since cloning, comparing and
+/// dropping a tree recurse once per level
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 4096,
+            max_atom_len: usize::MAX,
+            max_total_atom_bytes: usize::MAX,
+        }
+    }
+}
+
+impl BSExp {
+    /// Check the tree against [`Limits::default`]
+    pub fn validate(&self) -> Result<(), BseError> {
+        self.validate_with(&Limits::default())
+    }
+
+    /// Check the tree against `limits` before handing it on,
+    /// reporting the first limit found to be exceeded
+    pub fn validate_with(&self, limits: &Limits) -> Result<(), BseError> {
+        let mut total = 0usize;
+        let mut stack = vec![(self, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            if depth > limits.max_depth {
+                return Err(BseError::DepthLimitExceeded(limits.max_depth));
+            }
+            match node {
+                BSExp::Atom(a) => {
+                    if a.len() > limits.max_atom_len {
+                        return Err(BseError::AtomTooLong {
+                            len: a.len(),
+                            max: limits.max_atom_len,
+                        });
+                    }
+                    total = total.saturating_add(a.len());
+                    if total > limits.max_total_atom_bytes {
+                        return Err(BseError::SizeLimitExceeded(limits.max_total_atom_bytes));
+                    }
+                }
+                BSExp::List(v) => stack.extend(v.iter().map(|x| (x, depth + 1))),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_validate() {
+    let fib = crate::fib_example();
+    assert_eq!(fib.validate(), Ok(()));
+
+    let limits = Limits {
+        max_depth: 3,
+        ..Limits::default()
+    };
+    assert_eq!(
+        fib.validate_with(&limits),
+        Err(BseError::DepthLimitExceeded(3))
+    );
+
+    let limits = Limits {
+        max_atom_len: 8,
+        ..Limits::default()
+    };
+    assert_eq!(
+        fib.validate_with(&limits),
+        Err(BseError::AtomTooLong { len: 9, max: 8 })
+    );
+
+    let limits = Limits {
+        max_total_atom_bytes: 76,
+        ..Limits::default()
+    };
+    assert_eq!(
+        fib.validate_with(&limits),
+        Err(BseError::SizeLimitExceeded(76))
+    );
+    let limits = Limits {
+        max_total_atom_bytes: 77,
+        ..Limits::default()
+    };
+    assert_eq!(fib.validate_with(&limits), Ok(()));
+}
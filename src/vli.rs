@@ -1,19 +1,54 @@
+use crate::BseError;
+use std::io::{self, Read};
 use std::iter::repeat_with;
 
 /// Variant-Length Integer
-/// This type can be serialized to 1 to 8 bytes
-/// encoding atmost 2^56 bit data
+/// This type can be serialized to 1 to 9 bytes
+/// - the first 8 bytes carry 7 bits each, little-endian, with the high bit
+///   set when another byte follows
+/// - a 9th byte, if present, carries the top 8 bits with no continuation flag
+///
+/// This is byte-identical to unsigned LEB128 for values below 2^63.
+/// From 2^63 on, LEB128 needs a 10th byte where VLI stores bit 63 in the
+/// high bit of the 9th; use [`push_leb128`] / [`decode_leb128`] when strict
+/// LEB128 is needed for interop, e.g. with WebAssembly or Protobuf tooling.
 pub trait VLI {
     fn to_vli_bytes(self) -> ([u8; 9], usize);
 
+    /// Encode into a caller-provided buffer, returning the bytes written
+    #[inline]
+    fn to_vli_slice(self, buf: &mut [u8; 9]) -> &[u8]
+    where
+        Self: Sized,
+    {
+        let (bytes, len) = self.to_vli_bytes();
+        buf[..len].copy_from_slice(&bytes[..len]);
+        &buf[..len]
+    }
+
     fn read_vli_bytes<F, E>(reader: F) -> Result<Self, E>
     where
         Self: Sized,
         F: FnMut() -> Result<u8, E>;
+
+    /// Same as [`VLI::read_vli_bytes`], also returning how many bytes were read
+    fn read_vli_counted<F, E>(mut reader: F) -> Result<(Self, usize), E>
+    where
+        Self: Sized,
+        F: FnMut() -> Result<u8, E>,
+    {
+        let mut count = 0;
+        Self::read_vli_bytes(|| {
+            count += 1;
+            reader()
+        })
+        .map(|v| (v, count))
+    }
 }
 
 impl VLI for u64 {
     #[inline]
+    #[allow(clippy::identity_op)]
     fn to_vli_bytes(self) -> ([u8; 9], usize) {
         let len = match self {
             _ if self < (0b1 << (1 * 7)) => 1,
@@ -45,6 +80,7 @@ impl VLI for u64 {
     }
 
     #[inline]
+    #[allow(clippy::identity_op)]
     fn read_vli_bytes<F, E>(mut reader: F) -> Result<Self, E>
     where
         F: FnMut() -> Result<u8, E>,
@@ -69,6 +105,71 @@ impl VLI for u64 {
     }
 }
 
+/// Append the VLI encoding of `value` to `buf`
+pub fn push_vli(buf: &mut Vec<u8>, value: u64) {
+    let (bytes, len) = value.to_vli_bytes();
+    buf.extend_from_slice(&bytes[0..len]);
+}
+
+/// Read one VLI from a byte iterator, `None` if it ends inside the VLI
+pub fn read_vli_from_iter<I: Iterator<Item = u8>>(iter: &mut I) -> Option<u64> {
+    u64::read_vli_bytes(|| iter.next().ok_or(())).ok()
+}
+
+/// Read one VLI from an [`io::Read`], one byte at a time
+/// - errors of the reader are returned as they are
+/// - EOF inside the VLI is an [`io::ErrorKind::UnexpectedEof`] error
+/// - wrap unbuffered readers in a [`io::BufReader`]
+pub fn read_vli_io<R: Read>(reader: &mut R) -> io::Result<u64> {
+    u64::read_vli_bytes(|| {
+        let mut byte = [0];
+        reader.read_exact(&mut byte).map(|()| byte[0])
+    })
+}
+
+/// Append the strict unsigned LEB128 encoding of `value` to `buf`,
+/// 1 to 10 bytes long
+pub fn push_leb128(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0b1000_0000 {
+        buf.push((value as u8 & 0b0111_1111) | 0b1000_0000);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Decode one strict unsigned LEB128 value from the start of `buf`,
+/// returning it with the number of bytes read
+/// - `Err(BseError::InvalidVli)` if the value does not fit in a `u64`
+pub fn decode_leb128(buf: &[u8]) -> Result<(u64, usize), BseError> {
+    let mut value = 0u64;
+    for (i, &b) in buf.iter().enumerate() {
+        let bits = (b & 0b0111_1111) as u64;
+        if (i == 9 && bits > 1) || i > 9 {
+            return Err(BseError::InvalidVli);
+        }
+        value |= bits << (i * 7);
+        if b & 0b1000_0000 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(BseError::TruncatedVli)
+}
+
+/// Decode successive VLIs from `buf` until it is exhausted
+/// - yields `Err(BseError::TruncatedVli)` once if the buffer ends inside a VLI
+pub fn decode_vli_stream(buf: &[u8]) -> impl Iterator<Item = Result<u64, BseError>> + '_ {
+    let mut bytes = buf.iter();
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed || bytes.as_slice().is_empty() {
+            return None;
+        }
+        let r = u64::read_vli_bytes(|| bytes.next().copied().ok_or(BseError::TruncatedVli));
+        failed = r.is_err();
+        Some(r)
+    })
+}
+
 #[test]
 fn test_vli_encode_decode() {
     // use core::convert::Infallible;
@@ -99,3 +200,194 @@ fn test_vli_encode_decode() {
             .fold(true, |acc, (l, r)| { acc && (l == r) })
     )
 }
+
+#[test]
+fn test_vli_decode_stream() {
+    let values = [0, 1, 127, 128, 300, 1 << 56, u64::MAX];
+    let mut buf = Vec::new();
+    values.iter().map(|i| i.to_vli_bytes()).for_each(|(v, l)| {
+        buf.extend_from_slice(&v[0..l]);
+    });
+    assert_eq!(
+        decode_vli_stream(&buf).collect::<Result<Vec<_>, _>>(),
+        Ok(values.to_vec())
+    );
+
+    buf.push(0b1000_0001);
+    let decoded = decode_vli_stream(&buf).collect::<Vec<_>>();
+    assert_eq!(decoded.len(), values.len() + 1);
+    assert_eq!(decoded.last(), Some(&Err(BseError::TruncatedVli)));
+}
+
+#[test]
+fn test_vli_read_counted() {
+    for (value, len) in [
+        (0, 1),
+        (127, 1),
+        (128, 2),
+        (300, 2),
+        (1 << 56, 9),
+        (u64::MAX, 9),
+    ] {
+        let (bytes, l) = value.to_vli_bytes();
+        let mut buf = bytes[0..l].iter().chain(&[0xAA, 0xBB]);
+        assert_eq!(
+            u64::read_vli_counted(|| buf.next().copied().ok_or(())),
+            Ok((value, len))
+        );
+        assert_eq!(buf.next(), Some(&0xAA));
+    }
+}
+
+#[test]
+fn test_vli_push() {
+    let values = [0, 300, 1 << 56, u64::MAX];
+    let mut pushed = Vec::new();
+    let mut sliced = Vec::new();
+    for v in values {
+        push_vli(&mut pushed, v);
+        let (bytes, len) = v.to_vli_bytes();
+        sliced.extend_from_slice(&bytes[0..len]);
+    }
+    assert_eq!(pushed, sliced);
+}
+
+#[test]
+fn test_vli_read_from_iter() {
+    let mut buf = Vec::new();
+    push_vli(&mut buf, 300);
+    push_vli(&mut buf, u64::MAX);
+    buf.push(0b1000_0000);
+    let mut it = buf.into_iter();
+    assert_eq!(read_vli_from_iter(&mut it), Some(300));
+    assert_eq!(read_vli_from_iter(&mut it), Some(u64::MAX));
+    assert_eq!(read_vli_from_iter(&mut it), None);
+}
+
+#[test]
+fn test_vli_leb128_compatibility() {
+    // reference unsigned LEB128 encoder, as in the DWARF specification
+    let reference = |mut v: u64| {
+        let mut out = Vec::new();
+        loop {
+            let byte = (v & 0x7f) as u8;
+            v >>= 7;
+            if v == 0 {
+                out.push(byte);
+                return out;
+            }
+            out.push(byte | 0x80);
+        }
+    };
+    let mut seed = 0x1234_5678_9ABC_DEF0u64;
+    let values = repeat_with(|| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        seed >> (seed % 64)
+    })
+    .take(1000)
+    .chain([
+        0,
+        127,
+        128,
+        (1 << 56) - 1,
+        1 << 56,
+        (1 << 63) - 1,
+        1 << 63,
+        u64::MAX,
+    ]);
+
+    for v in values {
+        let expected = reference(v);
+        let mut leb = Vec::new();
+        push_leb128(&mut leb, v);
+        assert_eq!(leb, expected);
+        assert_eq!(decode_leb128(&leb), Ok((v, leb.len())));
+
+        let mut vli = Vec::new();
+        push_vli(&mut vli, v);
+        if v < 1 << 63 {
+            assert_eq!(vli, expected);
+        } else {
+            assert_eq!((vli.len(), expected.len()), (9, 10));
+        }
+    }
+
+    assert_eq!(decode_leb128(&[0x80]), Err(BseError::TruncatedVli));
+    assert_eq!(
+        decode_leb128(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x02]),
+        Err(BseError::InvalidVli)
+    );
+}
+
+#[test]
+fn test_vli_to_slice() {
+    let mut buf = [0; 9];
+    for v in [0, 127, 128, 300, (1 << 56) - 1, 1 << 56, u64::MAX] {
+        let (bytes, len) = v.to_vli_bytes();
+        assert_eq!(v.to_vli_slice(&mut buf), &bytes[..len]);
+    }
+}
+
+#[test]
+fn test_vli_nine_byte_boundary() {
+    let boundary = 1u64 << 56;
+    assert_eq!((boundary - 1).to_vli_bytes().1, 8);
+    assert_eq!(boundary.to_vli_bytes().1, 9);
+
+    // every length change happens exactly at a multiple of 7 bits
+    for n in 1..=8 {
+        let first = 1u64 << (n * 7);
+        assert_eq!((first - 1).to_vli_bytes().1, n);
+        assert_eq!(first.to_vli_bytes().1, n + 1);
+    }
+
+    // walk [2^56, u64::MAX] with a stride that varies the low bits,
+    // plus the top of the range where the 9th byte is all ones
+    let mut seed = 0x9E37_79B9_7F4A_7C15u64;
+    let samples = repeat_with(|| {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+        boundary | seed
+    })
+    .take(10_000)
+    .chain((0..256).map(|k| u64::MAX - k))
+    .chain((0..256).map(|k| boundary + k))
+    .chain((1..=255u64).map(|top| (top << 56) | 0x7f));
+    for v in samples {
+        let (bytes, len) = v.to_vli_bytes();
+        assert_eq!(len, 9);
+        assert_eq!(bytes[8], (v >> 56) as u8);
+        let mut it = bytes.into_iter();
+        assert_eq!(u64::read_vli_counted(|| it.next().ok_or(())), Ok((v, 9)));
+    }
+}
+
+#[test]
+fn test_vli_read_io() {
+    struct Failing<'a>(&'a [u8]);
+    impl Read for Failing<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((&b, rest)) if !buf.is_empty() => {
+                    buf[0] = b;
+                    self.0 = rest;
+                    Ok(1)
+                }
+                _ => Err(io::Error::new(io::ErrorKind::ConnectionReset, "gone")),
+            }
+        }
+    }
+
+    let mut buf = Vec::new();
+    push_vli(&mut buf, 300);
+    push_vli(&mut buf, u64::MAX);
+    let mut cursor = io::Cursor::new(&buf);
+    assert_eq!(read_vli_io(&mut cursor).unwrap(), 300);
+    assert_eq!(read_vli_io(&mut cursor).unwrap(), u64::MAX);
+    let eof = read_vli_io(&mut io::Cursor::new(&buf[2..6])).unwrap_err();
+    assert_eq!(eof.kind(), io::ErrorKind::UnexpectedEof);
+
+    let mut failing = Failing(&buf[..1]);
+    let err = read_vli_io(&mut failing).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+    assert_eq!(err.to_string(), "gone");
+}